@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+
+use grep::matcher::{Match, Matcher, NoCaptures, NoError};
+
+/// Finds literal patterns within an edit distance, so "modern" still finds "m0dern" or "mod ern".
+/// The pattern has to be longer than the distance, or every position would match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatcher {
+    patterns: Vec<Vec<char>>,
+    pub distance: usize,
+    pub case_insensitive: bool,
+    /// Matches may run over line ends, any whitespace then matches a space in the pattern
+    pub multi_line: bool,
+}
+
+impl FuzzyMatcher {
+    pub fn new<P: AsRef<str>>(patterns: &[P], distance: usize, case_insensitive: bool, multi_line: bool) -> Result<FuzzyMatcher, String> {
+        let mut matcher = FuzzyMatcher { patterns: vec![], distance, case_insensitive, multi_line };
+        for pattern in patterns {
+            let chars: Vec<char> = pattern.as_ref().chars().map(|c| matcher.fold(c)).collect();
+            if chars.len() <= distance {
+                return Err(format!("--fuzzy {} needs patterns longer than {} characters: {}", distance, distance, pattern.as_ref()));
+            }
+            matcher.patterns.push(chars);
+        }
+        Ok(matcher)
+    }
+
+    fn fold(&self, c: char) -> char {
+        if self.multi_line && c.is_whitespace() {
+            ' '
+        } else if self.case_insensitive {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            c
+        }
+    }
+
+    /// Smallest edit distance between the text and one of the patterns.
+    pub fn edit_distance(&self, text: &str) -> usize {
+        let text: Vec<char> = text.chars().map(|c| self.fold(c)).collect();
+        self.patterns.iter().map(|pattern| levenshtein(pattern, &text)).min().unwrap_or(0)
+    }
+
+    /// Byte range of the first match of `pattern` in the text, the text is only read up to the match.
+    /// With `limit` it stops once a match could only start after that offset.
+    fn find_pattern(&self, pattern: &[char], text: &str, limit: Option<usize>) -> Option<(usize, usize)> {
+        // Sellers: column of edit distances between the pattern prefixes and the best text suffix
+        let mut column: Vec<usize> = (0..=pattern.len()).collect();
+        let mut chars: Vec<(usize, char)> = vec![];
+        // ends of the match with their distance, "theo", "theor" and "theory" all end a match for "theroy"
+        let mut ends: Vec<(usize, usize)> = vec![];
+        for (offset, c) in text.char_indices() {
+            let c = self.fold(c);
+            if c == '\n' && !self.multi_line {
+                if !ends.is_empty() {
+                    break;
+                }
+                column = (0..=pattern.len()).collect();
+                chars.clear();
+                continue;
+            }
+            if let Some(limit) = limit.filter(|_| ends.is_empty()) {
+                // a match ending here or later is at most pattern.len() + distance characters long
+                let earliest = (chars.len() + 1).saturating_sub(pattern.len() + self.distance);
+                if chars.get(earliest).map_or(offset, |(start, _)| *start) > limit {
+                    break;
+                }
+            }
+            chars.push((offset, c));
+            let mut diagonal = column[0];
+            for i in 1..=pattern.len() {
+                let cost = usize::from(pattern[i - 1] != c);
+                let value = (diagonal + cost).min(column[i] + 1).min(column[i - 1] + 1);
+                diagonal = column[i];
+                column[i] = value;
+            }
+
+            let score = column[pattern.len()];
+            if score <= self.distance {
+                ends.push((chars.len() - 1, score));
+            } else if !ends.is_empty() {
+                break;
+            }
+        }
+
+        // the smallest distance, then the length closest to the pattern
+        let (start, end) = ends.iter().filter_map(|(end, score)| {
+            let shortest = (end + 1).saturating_sub(pattern.len() + self.distance);
+            (shortest..=*end)
+                .filter(|start| levenshtein(pattern, &chars[*start..=*end].iter().map(|(_, c)| *c).collect::<Vec<_>>()) == *score)
+                .map(|start| (*score, (end + 1 - start).abs_diff(pattern.len()), start, *end))
+                .min()
+        }).min().map(|(_, _, start, end)| (start, end))?;
+        let last = chars[end].0;
+        Some((chars[start].0, last + text[last..].chars().next().map_or(0, char::len_utf8)))
+    }
+}
+
+/// Scan of a pattern from `from` on, with the byte range of its first match.
+#[derive(Debug, Clone, Copy)]
+struct Scan {
+    from: usize,
+    found: Option<(usize, usize)>,
+}
+
+struct LastScans {
+    haystack: Vec<u8>,
+    matchers: Vec<(FuzzyMatcher, Vec<Option<Scan>>)>,
+}
+
+thread_local! {
+    /// Scans of the last haystack on this thread. `find_iter` calls `find_at` after every match, a pattern
+    /// with its next match further on or none at all is not scanned again for each of them.
+    static LAST_SCANS: RefCell<Option<LastScans>> = const { RefCell::new(None) };
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let value = (diagonal + usize::from(ca != cb)).min(row[j + 1] + 1).min(row[j] + 1);
+            diagonal = row[j + 1];
+            row[j + 1] = value;
+        }
+    }
+    row[b.len()]
+}
+
+impl Matcher for FuzzyMatcher {
+    type Captures = NoCaptures;
+    type Error = NoError;
+
+    fn find_at(&self, haystack: &[u8], at: usize) -> Result<Option<Match>, NoError> {
+        let mut at = at;
+        while haystack.get(at).is_some_and(|b| b & 0xC0 == 0x80) {
+            at += 1;
+        }
+        let text = match std::str::from_utf8(&haystack[at..]) {
+            Ok(text) => text,
+            Err(e) => std::str::from_utf8(&haystack[at..at + e.valid_up_to()]).unwrap_or_default(),
+        };
+
+        LAST_SCANS.with(|last| {
+            let mut last = last.borrow_mut();
+            // the content is compared, a new page can get the address of the last one
+            let last = match last.as_mut().filter(|last| last.haystack == haystack) {
+                Some(last) => last,
+                None => last.insert(LastScans { haystack: haystack.to_vec(), matchers: vec![] }),
+            };
+            let index = match last.matchers.iter().position(|(matcher, _)| matcher == self) {
+                Some(index) => index,
+                None => {
+                    last.matchers.push((self.clone(), vec![None; self.patterns.len()]));
+                    last.matchers.len() - 1
+                },
+            };
+            let scans = &mut last.matchers[index].1;
+
+            // a scan from before `at` still holds when it found nothing, or a match that starts at `at` or later
+            let still_valid = |scan: &Option<Scan>| scan.filter(|scan| scan.from <= at && scan.found.map_or(true, |(start, _)| start >= at));
+            // the leftmost match, on the same start the first pattern
+            let mut best: Option<(usize, usize, usize)> = None;
+            let consider = |best: &mut Option<(usize, usize, usize)>, start: usize, pattern: usize, end: usize| {
+                if best.map_or(true, |(best_start, best_pattern, _)| (start, pattern) < (best_start, best_pattern)) {
+                    *best = Some((start, pattern, end));
+                }
+            };
+            for (pattern, scan) in scans.iter().enumerate() {
+                if let Some(Scan { found: Some((start, end)), .. }) = still_valid(scan) {
+                    consider(&mut best, start, pattern, end);
+                }
+            }
+            for (index, pattern) in self.patterns.iter().enumerate() {
+                if still_valid(&scans[index]).is_some() {
+                    continue;
+                }
+                let limit = best.map(|(start, _, _)| start - at);
+                match self.find_pattern(pattern, text, limit) {
+                    Some((start, end)) => {
+                        scans[index] = Some(Scan { from: at, found: Some((at + start, at + end)) });
+                        consider(&mut best, at + start, index, at + end);
+                    },
+                    // a scan stopped at the limit says nothing about the text after it
+                    None if limit.is_none() => scans[index] = Some(Scan { from: at, found: None }),
+                    None => {},
+                }
+            }
+            Ok(best.map(|(start, _, end)| Match::new(start, end)))
+        })
+    }
+
+    fn new_captures(&self) -> Result<NoCaptures, NoError> {
+        Ok(NoCaptures::new())
+    }
+}
+
+#[test]
+fn test_fuzzy_matcher() {
+    let matcher = FuzzyMatcher::new(&["modern", "quantum field"], 1, true, false).unwrap();
+    let text = "the mod ern\nQuantum fie1d and a quantumfield";
+    let mut found = vec![];
+    matcher.find_iter(text.as_bytes(), |m| { found.push((&text[m], matcher.edit_distance(&text[m]))); true }).unwrap();
+    assert_eq!(found, [("mod ern", 1), ("Quantum fie1d", 1), ("quantumfield", 1)]);
+
+    assert!(FuzzyMatcher::new(&["ab"], 2, true, false).is_err());
+}
+
+#[test]
+fn test_fuzzy_matcher_inside_char() {
+    let matcher = FuzzyMatcher::new(&["theory"], 1, true, false).unwrap();
+    let text = "é theorx";
+    // 1 is inside the "é", the search starts at the next character
+    assert_eq!(matcher.find_at(text.as_bytes(), 1).unwrap(), Some(Match::new(3, 9)));
+}
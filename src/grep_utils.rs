@@ -1,18 +1,546 @@
-use std::borrow::BorrowMut;
+use std::borrow::{BorrowMut, Cow};
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
 
 use grep::{
-    printer::Standard,
-    regex::RegexMatcher, searcher::Searcher};
-use termcolor::BufferedStandardStream;
+    matcher::{Match, Matcher, NoCaptures},
+    printer::{ColorSpecs, Standard, SummaryKind},
+    searcher::{Searcher, Sink, SinkContext, SinkContextKind, SinkError, SinkFinish, SinkMatch}};
+use serde_json::json;
+use termcolor::WriteColor;
 
-use crate::settings::Settings;
+use crate::normalize::{FoldedMatcher, FoldedPage};
+use crate::patterns::PatternSet;
+use crate::settings::{Settings, MAX_COLUMNS};
 
-pub fn search_pdf_page(matcher: &RegexMatcher, printer: &mut Standard<BufferedStandardStream>, from: &[u8], page: usize, _settings: &Settings) -> Result<u32, Box<dyn std::error::Error>> {
-   
-    let p = format!("Page: {}", page+1);
-    let mut sink = printer.sink_with_path(matcher, p.as_str());
-    Searcher::new().search_slice(matcher, from, sink.borrow_mut())?;
-    let stats = sink.stats().unwrap();
+/// With `crossing`, the page break in a page searched together with the start of the next page,
+/// only the matches running over it are kept. Without it all matches of the inner matcher are kept.
+/// The searcher still finds every match, `PageSpanSink` drops the lines without a crossing one and
+/// sets `haystack_start` while the sink behind it looks for the matches to print.
+pub struct PageSpanMatcher<'a, M: Matcher> {
+    pub inner: &'a M,
+    pub crossing: Option<usize>,
+    /// Offset in the page text of the haystack passed to `find_at`
+    haystack_start: Cell<Option<usize>>,
+}
+
+impl<'a, M: Matcher> PageSpanMatcher<'a, M> {
+    pub fn new(inner: &'a M, crossing: Option<usize>) -> Self {
+        PageSpanMatcher { inner, crossing, haystack_start: Cell::new(None) }
+    }
+}
+
+impl<'a, M: Matcher> Matcher for PageSpanMatcher<'a, M> {
+    type Captures = NoCaptures;
+    type Error = M::Error;
+
+    fn find_at(&self, haystack: &[u8], at: usize) -> Result<Option<Match>, M::Error> {
+        let (Some(boundary), Some(offset)) = (self.crossing, self.haystack_start.get()) else {
+            return self.inner.find_at(haystack, at);
+        };
+
+        let mut at = at;
+        while at <= haystack.len() {
+            let Some(m) = self.inner.find_at(haystack, at)? else { break };
+            if offset + m.start() >= boundary {
+                break;
+            }
+            if offset + m.end() > boundary {
+                return Ok(Some(m));
+            }
+            // on to the next character, a match can't start inside one
+            at = m.start() + 1;
+            while haystack.get(at).is_some_and(|b| b & 0xC0 == 0x80) {
+                at += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    fn new_captures(&self) -> Result<NoCaptures, M::Error> {
+        Ok(NoCaptures::new())
+    }
+}
+
+/// Passes the lines with a match over the page break on to `inner`, see `PageSpanMatcher`.
+/// Context lines are left out then, they could belong to a dropped match as well.
+pub struct PageSpanSink<'a, 'm, M: Matcher, S: Sink> {
+    pub matcher: &'a PageSpanMatcher<'m, M>,
+    pub inner: S,
+}
+
+impl<'a, 'm, M: Matcher, S: Sink> Sink for PageSpanSink<'a, 'm, M, S> {
+    type Error = S::Error;
+
+    fn matched(&mut self, searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, S::Error> {
+        if self.matcher.crossing.is_none() {
+            return self.inner.matched(searcher, mat);
+        }
+        // the sinks search the buffer up to the end of the lines, like the grep printers
+        let range = mat.bytes_range_in_buffer();
+        self.matcher.haystack_start.set(Some(mat.absolute_byte_offset() as usize - range.start));
+        let result = match self.matcher.find_at(&mat.buffer()[..range.end], range.start) {
+            Ok(Some(m)) if m.start() < range.end => self.inner.matched(searcher, mat),
+            Ok(_) => Ok(true),
+            Err(e) => Err(S::Error::error_message(e)),
+        };
+        self.matcher.haystack_start.set(None);
+        result
+    }
+
+    fn context(&mut self, searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, S::Error> {
+        match self.matcher.crossing {
+            Some(_) => Ok(true),
+            None => self.inner.context(searcher, context),
+        }
+    }
+
+    fn context_break(&mut self, searcher: &Searcher) -> Result<bool, S::Error> {
+        match self.matcher.crossing {
+            Some(_) => Ok(true),
+            None => self.inner.context_break(searcher),
+        }
+    }
+
+    fn begin(&mut self, searcher: &Searcher) -> Result<bool, S::Error> {
+        self.inner.begin(searcher)
+    }
+
+    fn finish(&mut self, searcher: &Searcher, finish: &SinkFinish) -> Result<(), S::Error> {
+        self.inner.finish(searcher, finish)
+    }
+}
+
+/// A page to search, `page` is the 0-based physical index.
+pub struct PdfPage<'a> {
+    pub text: &'a [u8],
+    pub page: usize,
+    /// Start of the overlap from the next page in `text`, see `PageSpanMatcher`
+    pub boundary: Option<usize>,
+    /// Printed labels of all pages of the file, with `--page-labels`
+    pub labels: Option<&'a [String]>,
+}
+
+impl<'a> PdfPage<'a> {
+    pub fn new(text: &'a [u8], page: usize) -> Self {
+        PdfPage { text, page, boundary: None, labels: None }
+    }
+
+    /// The page without the overlap from the next page.
+    pub fn without_overlap(&self) -> PdfPage<'a> {
+        let end = self.boundary.unwrap_or(self.text.len());
+        PdfPage { text: &self.text[..end], page: self.page, boundary: None, labels: self.labels }
+    }
+
+    pub fn label(&self, page: usize) -> Option<&'a str> {
+        self.labels.and_then(|labels| labels.get(page)).map(String::as_str)
+    }
+
+    /// "Page: 23 (ix)", or "Page: 12-13 (x-xi)" for the matches running over the page break.
+    pub fn heading(&self, crossing: bool) -> String {
+        let (numbers, labels) = if crossing {
+            (format!("{}-{}", self.page+1, self.page+2), self.label(self.page).zip(self.label(self.page+1)).map(|(a, b)| format!("{}-{}", a, b)))
+        } else {
+            ((self.page+1).to_string(), self.label(self.page).map(str::to_string))
+        };
+        match labels {
+            Some(labels) => format!("Page: {} ({})", numbers, labels),
+            None => format!("Page: {}", numbers),
+        }
+    }
+}
+
+/// Prints the matches of a page. With a `boundary` the text goes on with the start of the next page,
+/// matches running over the page break are then printed under "Page: N-N+1".
+pub fn search_pdf_page<M: Matcher, W: WriteColor>(matcher: &M, printer: &mut Standard<W>, page: &PdfPage, settings: &Settings) -> Result<u32, Box<dyn std::error::Error>> {
+    // the matches on the page end before the break, the overlap is only searched for the ones running over it
+    let mut count = search_with_path(matcher, printer, &page.without_overlap(), None, settings)?;
+    if let Some(boundary) = page.boundary {
+        count += search_with_path(matcher, printer, page, Some(boundary), settings)?;
+    }
+    Ok(count)
+}
+
+fn search_with_path<M: Matcher, W: WriteColor>(matcher: &M, printer: &mut Standard<W>, page: &PdfPage, crossing: Option<usize>, settings: &Settings) -> Result<u32, Box<dyn std::error::Error>> {
+    let matcher = PageSpanMatcher::new(matcher, crossing);
+    let path = page.heading(crossing.is_some());
+    if let Some(patterns) = settings.match_labels() {
+        let inner = TaggedSink {
+            matcher: &matcher, patterns, wtr: printer.get_mut(), colors: &settings.search_color_specs,
+            heading: &path, separator: format!("-- Page: {} --", page.page + 1), has_written: false, matches: 0,
+        };
+        let mut sink = PageSpanSink { matcher: &matcher, inner };
+        settings.create_searcher().search_slice(&matcher, page.text, &mut sink)?;
+        return Ok(sink.inner.matches as u32);
+    }
+
+    let mut sink = PageSpanSink { matcher: &matcher, inner: printer.sink_with_path(&matcher, &path) };
+    settings.create_searcher().search_slice(&matcher, page.text, sink.borrow_mut())?;
+    let stats = sink.inner.stats().unwrap();
 
     Ok(stats.matches() as u32)
-}
\ No newline at end of file
+}
+
+/// The matches in the lines of `mat`, relative to the lines. Like the grep printers the matcher gets
+/// the search buffer up to the end of the lines, so `PageSpanMatcher` knows where it starts.
+fn find_in_lines<M: Matcher, F: FnMut(Match)>(matcher: &M, mat: &SinkMatch<'_>, mut found: F) -> io::Result<()> {
+    let range = mat.bytes_range_in_buffer();
+    matcher.find_iter_at(&mat.buffer()[..range.end], range.start, |m| {
+        if m.start() >= range.end {
+            return false;
+        }
+        found(Match::new(m.start() - range.start, m.end() - range.start));
+        true
+    }).map_err(|e| io::Error::other(e.to_string()))
+}
+
+/// Standard output with the labels of the matches in front of their line, the pattern with `--tag` or
+/// the `--fuzzy` distance. Like the printer it writes the page heading before the first match, every
+/// line once with all its matches highlighted and cuts lines longer than `MAX_COLUMNS`.
+pub struct TaggedSink<'a, M: Matcher, W: WriteColor> {
+    pub matcher: M,
+    pub patterns: &'a PatternSet,
+    pub wtr: &'a mut W,
+    pub colors: &'a ColorSpecs,
+    pub heading: &'a str,
+    pub separator: String,
+    pub has_written: bool,
+    pub matches: u64,
+}
+
+impl<'a, M: Matcher, W: WriteColor> TaggedSink<'a, M, W> {
+    fn write_heading(&mut self) -> io::Result<()> {
+        if !self.has_written {
+            self.has_written = true;
+            self.wtr.set_color(self.colors.path())?;
+            writeln!(self.wtr, "{}", self.heading)?;
+            self.wtr.reset()?;
+        }
+        Ok(())
+    }
+
+    fn write_line_number(&mut self, line_number: Option<u64>, separator: char) -> io::Result<()> {
+        if let Some(line_number) = line_number {
+            self.wtr.set_color(self.colors.line())?;
+            write!(self.wtr, "{}", line_number)?;
+            self.wtr.reset()?;
+            write!(self.wtr, "{}", separator)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `line` starting at `line_start` in the matched lines, cut at `MAX_COLUMNS`.
+    fn write_matched_line(&mut self, line: &[u8], line_start: usize, found: &[Match]) -> io::Result<()> {
+        let content = line.strip_suffix(b"\n").unwrap_or(line).len();
+        let mut shown = content.min(MAX_COLUMNS);
+        while shown < content && line[shown] & 0xC0 == 0x80 {
+            shown -= 1;
+        }
+
+        let mut written = 0;
+        for m in found {
+            let start = m.start().clamp(line_start + written, line_start + shown) - line_start;
+            let end = m.end().clamp(line_start + written, line_start + shown) - line_start;
+            if start == end {
+                continue;
+            }
+            self.wtr.write_all(&line[written..start])?;
+            self.wtr.set_color(self.colors.matched())?;
+            self.wtr.write_all(&line[start..end])?;
+            self.wtr.reset()?;
+            written = end;
+        }
+        if shown == content {
+            return self.write_lines(&line[written..]);
+        }
+
+        self.wtr.write_all(&line[written..shown])?;
+        let more = found.iter().filter(|m| (line_start + shown..line_start + line.len()).contains(&m.start())).count();
+        match more {
+            0 => writeln!(self.wtr, " [... omitted end of long line]"),
+            1 => writeln!(self.wtr, " [... 1 more match]"),
+            more => writeln!(self.wtr, " [... {} more matches]", more),
+        }
+    }
+
+    fn write_lines(&mut self, lines: &[u8]) -> io::Result<()> {
+        self.wtr.write_all(lines)?;
+        if !lines.ends_with(b"\n") {
+            self.wtr.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, M: Matcher, W: WriteColor> Sink for TaggedSink<'a, M, W> {
+    type Error = io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, io::Error> {
+        let lines = mat.bytes();
+        let mut found = vec![];
+        find_in_lines(&self.matcher, mat, |m| found.push(m))?;
+
+        self.matches += found.len() as u64;
+
+        // with --multiline the lines of several matches come at once, a line gets the labels of the matches starting in it
+        self.write_heading()?;
+        let mut line_start = 0;
+        for (i, line) in lines.split_inclusive(|b| *b == b'\n').enumerate() {
+            self.write_line_number(mat.line_number().map(|number| number + i as u64), ':')?;
+            let mut labels: Vec<String> = vec![];
+            for m in found.iter().filter(|m| (line_start..line_start + line.len()).contains(&m.start())) {
+                let label = self.patterns.label(lines, *m);
+                if !labels.contains(&label) {
+                    labels.push(label);
+                }
+            }
+            if !labels.is_empty() {
+                self.wtr.set_color(self.colors.path())?;
+                write!(self.wtr, "[{}]", labels.join(","))?;
+                self.wtr.reset()?;
+                self.wtr.write_all(b" ")?;
+            }
+            self.write_matched_line(line, line_start, &found)?;
+            line_start += line.len();
+        }
+        Ok(true)
+    }
+
+    fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, io::Error> {
+        self.write_heading()?;
+        self.write_line_number(context.line_number(), '-')?;
+        self.write_lines(context.bytes())?;
+        Ok(true)
+    }
+
+    fn context_break(&mut self, _searcher: &Searcher) -> Result<bool, io::Error> {
+        writeln!(self.wtr, "{}", self.separator)?;
+        Ok(true)
+    }
+}
+
+/// Short result of a file for -l, -L, -c and -q. The pages are searched as one text,
+/// with `--count=page` every page gets its own "path:page:count" line.
+pub fn search_pdf_summary<M: FoldedMatcher, W: WriteColor>(matcher: &M, wtr: W, path: &Path, pages: &[(usize, Cow<str>)], kind: SummaryKind, settings: &Settings) -> io::Result<u32> {
+    let mut printer = settings.create_summary_printer(kind, wtr);
+    let mut searcher = settings.create_searcher();
+    if settings.count_pages {
+        let mut total = 0;
+        for (page, text) in pages {
+            let page_path = format!("{}:{}", path.display(), page + 1);
+            let folded = FoldedPage::new(text, &settings.fold_options);
+            let matcher = folded.matcher(matcher);
+            let mut sink = printer.sink_with_path(&matcher, &page_path);
+            searcher.search_slice(&matcher, text.as_bytes(), &mut sink)?;
+            total += sink.stats().map_or(0, |stats| stats.matches());
+        }
+        return Ok(total as u32);
+    }
+
+    let text = pages.iter().map(|(_, text)| text.as_ref()).collect::<Vec<_>>().join("\n");
+    let folded = FoldedPage::new(&text, &settings.fold_options);
+    let matcher = folded.matcher(matcher);
+    let mut sink = printer.sink_with_path(&matcher, path);
+    searcher.search_slice(&matcher, text.as_bytes(), &mut sink)?;
+    let matches = sink.stats().map_or(0, |stats| stats.matches());
+    Ok(matches as u32)
+}
+
+/// Writes one JSON line per matching line of a page, with the file path and the 1-based page number.
+/// Line numbers and byte offsets are those of the searched page text: dehyphenation moved the rest of
+/// a broken word up a line and the first lines of the next page follow the page break.
+pub struct JsonPageSink<'a, M: Matcher, W: Write> {
+    pub matcher: M,
+    /// Adds the pattern or the `--fuzzy` distance of each submatch
+    pub patterns: Option<&'a PatternSet>,
+    pub wtr: &'a mut W,
+    pub path: &'a str,
+    pub page: &'a PdfPage<'a>,
+    /// Matches running over the page break, they also get the next page
+    pub crossing: bool,
+    pub matches: u64,
+    pub matched_lines: u64,
+}
+
+impl<'a, M: Matcher, W: Write> JsonPageSink<'a, M, W> {
+    fn add_pages(&self, record: &mut serde_json::Value) {
+        let page = self.page.page;
+        if self.crossing {
+            record["data"]["end_page"] = json!(page + 2);
+        }
+        if let Some(label) = self.page.label(page) {
+            record["data"]["page_label"] = json!(label);
+        }
+        if let Some(label) = self.page.label(page + 1).filter(|_| self.crossing) {
+            record["data"]["end_page_label"] = json!(label);
+        }
+    }
+}
+
+impl<'a, M: Matcher, W: Write> Sink for JsonPageSink<'a, M, W> {
+    type Error = io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, io::Error> {
+        let line = mat.bytes();
+        let mut submatches = vec![];
+        find_in_lines(&self.matcher, mat, |m| {
+            let mut submatch = json!({
+                "match": String::from_utf8_lossy(&line[m]),
+                "start": m.start(),
+                "end": m.end(),
+            });
+            if let Some(patterns) = self.patterns.filter(|patterns| patterns.tags) {
+                submatch["pattern"] = json!(patterns.tag(line, m));
+            }
+            if let Some(distance) = self.patterns.and_then(|patterns| patterns.distance(line, m)) {
+                submatch["distance"] = json!(distance);
+            }
+            submatches.push(submatch);
+        })?;
+
+        self.matches += submatches.len() as u64;
+        self.matched_lines += 1;
+        let mut record = json!({
+            "type": "match",
+            "data": {
+                "path": self.path,
+                "page": self.page.page + 1,
+                "line_number": mat.line_number(),
+                "absolute_offset": mat.absolute_byte_offset(),
+                "lines": String::from_utf8_lossy(line),
+                "submatches": submatches,
+            }
+        });
+        self.add_pages(&mut record);
+        write_json_line(self.wtr, &record)?;
+        Ok(true)
+    }
+
+    fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, io::Error> {
+        if let SinkContextKind::Other = context.kind() {
+            return Ok(true);
+        }
+        let mut record = json!({
+            "type": "context",
+            "data": {
+                "path": self.path,
+                "page": self.page.page + 1,
+                "line_number": context.line_number(),
+                "absolute_offset": context.absolute_byte_offset(),
+                "lines": String::from_utf8_lossy(context.bytes()),
+                "submatches": [],
+            }
+        });
+        self.add_pages(&mut record);
+        write_json_line(self.wtr, &record)?;
+        Ok(true)
+    }
+}
+
+pub fn write_json_line<W: Write>(wtr: &mut W, value: &serde_json::Value) -> io::Result<()> {
+    serde_json::to_writer(&mut *wtr, value)?;
+    wtr.write_all(b"\n")
+}
+
+pub fn search_pdf_page_json<M: Matcher, W: Write>(matcher: &M, wtr: &mut W, page: &PdfPage, path: &str, settings: &Settings) -> Result<(u64, u64), Box<dyn std::error::Error>> {
+    let (mut matches, mut lines) = search_json(matcher, &mut *wtr, &page.without_overlap(), path, None, settings)?;
+    if let Some(boundary) = page.boundary {
+        let (crossing_matches, crossing_lines) = search_json(matcher, wtr, page, path, Some(boundary), settings)?;
+        matches += crossing_matches;
+        lines += crossing_lines;
+    }
+    Ok((matches, lines))
+}
+
+fn search_json<M: Matcher, W: Write>(matcher: &M, wtr: &mut W, page: &PdfPage, path: &str, crossing: Option<usize>, settings: &Settings) -> Result<(u64, u64), Box<dyn std::error::Error>> {
+    let matcher = PageSpanMatcher::new(matcher, crossing);
+    let inner = JsonPageSink { matcher: &matcher, patterns: settings.match_labels(), wtr, path, page, crossing: crossing.is_some(), matches: 0, matched_lines: 0 };
+    let mut sink = PageSpanSink { matcher: &matcher, inner };
+    settings.create_searcher().search_slice(&matcher, page.text, &mut sink)?;
+    Ok((sink.inner.matches, sink.inner.matched_lines))
+}
+
+/// Run summary after all files, `matches` holds the match count of every searched file.
+pub fn write_json_summary(matches: &[u32], elapsed: Duration) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    write_json_line(&mut stdout, &json!({
+        "type": "summary",
+        "data": {
+            "elapsed_total": { "secs": elapsed.as_secs(), "nanos": elapsed.subsec_nanos(), "human": format!("{:.6}s", elapsed.as_secs_f64()) },
+            "stats": {
+                "searches": matches.len(),
+                "searches_with_match": matches.iter().filter(|m| **m > 0).count(),
+                "matches": matches.iter().map(|m| *m as u64).sum::<u64>(),
+            }
+        }
+    }))?;
+    stdout.flush()
+}
+
+#[test]
+fn test_json_page_sink() {
+    use grep::regex::RegexMatcher;
+    let matcher = RegexMatcher::new("(?i)theory").unwrap();
+    let mut buffer = vec![];
+    let (matches, lines) = search_pdf_page_json(&matcher, &mut buffer, &PdfPage::new(b"field theory\nno match\nTheory and theory\n", 11), "book.pdf", &Settings::default()).unwrap();
+    assert_eq!((matches, lines), (3, 2));
+
+    let records: Vec<serde_json::Value> = buffer.split(|b| *b == b'\n')
+        .filter(|l| !l.is_empty())
+        .map(|l| serde_json::from_slice(l).unwrap())
+        .collect();
+    assert_eq!(records[0]["data"]["page"], 12);
+    assert_eq!(records[1]["data"]["line_number"], 3);
+    assert_eq!(records[1]["data"]["submatches"][1]["start"], 11);
+}
+
+#[test]
+fn test_json_page_context() {
+    use grep::regex::RegexMatcher;
+    let matcher = RegexMatcher::new("theory").unwrap();
+    let settings = Settings { before_context: 1, after_context: 1, ..Default::default() };
+    let mut buffer = vec![];
+    search_pdf_page_json(&matcher, &mut buffer, &PdfPage::new(b"one\ntwo\nfield theory\nthree\nfour\n", 0), "book.pdf", &settings).unwrap();
+
+    let kinds: Vec<String> = buffer.split(|b| *b == b'\n')
+        .filter(|l| !l.is_empty())
+        .map(|l| serde_json::from_slice::<serde_json::Value>(l).unwrap()["type"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(kinds, ["context", "match", "context"]);
+}
+
+#[test]
+fn test_page_span_matcher() {
+    let settings = Settings { multiline: true, ..Default::default() };
+    let matcher = settings.create_matcher("field theory").unwrap();
+    // page 1 ends with "quantum field", the overlap from page 2 starts with "theory", page 2 has one more hit
+    let labels = ["xi".to_string(), "xii".to_string()];
+    let page = PdfPage { text: b"quantum field\ntheory of fields\nfield theory\n", page: 0, boundary: Some(14), labels: Some(&labels) };
+
+    let mut buffer = vec![];
+    let (matches, _) = search_pdf_page_json(&matcher, &mut buffer, &page, "book.pdf", &settings).unwrap();
+    assert_eq!(matches, 1);
+    let record: serde_json::Value = serde_json::from_slice(buffer.split(|b| *b == b'\n').next().unwrap()).unwrap();
+    assert_eq!((record["data"]["page"].as_u64(), record["data"]["end_page"].as_u64()), (Some(1), Some(2)));
+    assert_eq!(record["data"]["end_page_label"], "xii");
+    assert_eq!(page.heading(true), "Page: 1-2 (xi-xii)");
+}
+
+#[test]
+fn test_tagged_sink() {
+    let mut settings = Settings { tag_patterns: true, ..Default::default() };
+    settings.patterns = Some(PatternSet::new(vec!["aspirin".into(), r"ibu\w+".into()], &settings).unwrap());
+    let matcher = &settings.patterns.as_ref().unwrap().matcher;
+    let text = format!("aspirin or ibuprofen\n{}aspirin\n", "x".repeat(MAX_COLUMNS));
+
+    let mut buffer = vec![];
+    let mut printer = settings.create_page_printer(termcolor::NoColor::new(&mut buffer), 0);
+    let count = search_pdf_page(matcher, &mut printer, &PdfPage::new(text.as_bytes(), 0), &settings).unwrap();
+    assert_eq!(count, 3);
+    let output = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[..2], ["Page: 1", r"1:[aspirin,ibu\w+] aspirin or ibuprofen"]);
+    assert!(lines[2].starts_with("2:[aspirin] xxx") && lines[2].ends_with("x [... 1 more match]"));
+}
@@ -0,0 +1,303 @@
+use std::{collections::{BTreeMap, BTreeSet}, fs, io::Write, path::{Path, PathBuf}, time::UNIX_EPOCH};
+
+use directories::ProjectDirs;
+use grep::regex::RegexMatcher;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use termcolor::WriteColor;
+
+use crate::{grep_utils::{self, PdfPage}, normalize::{self, FoldedPage, FoldingMatcher}, pdf_tools::{self, Skipped}, settings::Settings, utils::{self, FNV_OFFSET}, BoxError};
+
+const INDEX_FOLDER_NAME: &str = "pdf_grep";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct IndexedFile {
+    pub id: u32,
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub pages: usize,
+}
+
+/// Page of a file containing a term, `page` is 0-based like the page split.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Posting {
+    pub file: u32,
+    pub page: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct BuildStats {
+    pub files: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub failed: usize,
+    /// Over `--max-filesize` or `--max-pages`, or out of `--timeout`
+    pub skipped: usize,
+}
+
+/// Inverted index of the words per page for all pdf files below `root`.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct PdfIndex {
+    pub root: PathBuf,
+    /// Folder of the indexes in the data dir, not saved with the index
+    #[serde(skip)]
+    pub folder: PathBuf,
+    pub next_id: u32,
+    pub files: BTreeMap<String, IndexedFile>,
+    pub terms: BTreeMap<String, Vec<Posting>>,
+}
+
+/// Lowercase alphanumeric words, the same for the indexed text and the query.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+fn file_state(file_path: &Path) -> Result<(u64, u64, u32), BoxError> {
+    let meta = fs::metadata(file_path)?;
+    let mtime = meta.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok((meta.len(), mtime.as_secs(), mtime.subsec_nanos()))
+}
+
+impl PdfIndex {
+    /// Location of the index of `root` in `folder`.
+    pub fn index_path(&self) -> PathBuf {
+        let key = utils::fnv1a(FNV_OFFSET, self.root.to_string_lossy().as_bytes());
+        self.folder.join(format!("{:016x}.json", key))
+    }
+
+    /// The index of `root` in the data dir, an empty one if there is none yet.
+    pub fn load(root: &Path) -> Result<PdfIndex, BoxError> {
+        let proj_dirs = ProjectDirs::from("", "", INDEX_FOLDER_NAME).ok_or("Could not find the data folder")?;
+        let index = PdfIndex { root: root.canonicalize()?, folder: proj_dirs.data_dir().join("index"), ..Default::default() };
+        match fs::read(index.index_path()) {
+            Ok(data) => Ok(PdfIndex { folder: index.folder, ..serde_json::from_slice(&data)? }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(index),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Extracted text of an indexed file, kept next to the index so a search needs no extraction.
+    fn text_path(&self, id: u32) -> PathBuf {
+        self.index_path().with_extension("texts").join(format!("{}.txt", id))
+    }
+
+    /// Text of an indexed file, extracted again for an index built before the texts were kept.
+    fn read_text(&self, file: &str, settings: &Settings) -> Result<Vec<u8>, BoxError> {
+        let id = self.files.get(file).ok_or("not in the index")?.id;
+        match fs::read(self.text_path(id)) {
+            Ok(text) => Ok(text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => pdf_tools::extract_checked(Path::new(file), settings),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    pub fn save(&self) -> Result<PathBuf, BoxError> {
+        let index_path = self.index_path();
+        if let Some(folder) = index_path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        fs::write(&index_path, serde_json::to_vec(self)?)?;
+        Ok(index_path)
+    }
+
+    /// Drops the postings and texts of the files with the `ids`, in one pass over the terms.
+    fn remove_files(&mut self, ids: &BTreeSet<u32>) {
+        if ids.is_empty() {
+            return;
+        }
+        self.terms.retain(|_, postings| {
+            postings.retain(|p| !ids.contains(&p.file));
+            !postings.is_empty()
+        });
+        for id in ids {
+            let _ = fs::remove_file(self.text_path(*id));
+        }
+    }
+
+    fn add_pages(&mut self, id: u32, pages: &[BTreeSet<String>]) {
+        for (page, words) in pages.iter().enumerate() {
+            for word in words {
+                self.terms.entry(word.clone()).or_default().push(Posting { file: id, page: page as u32 });
+            }
+        }
+    }
+
+    /// Re-extracts only new and changed files, drops the files that are gone.
+    pub fn update(&mut self, settings: &Settings) -> Result<BuildStats, BoxError> {
+        let pdf_files = utils::get_folder_files(&self.root, &settings.walk)?;
+        let mut stats = BuildStats { files: pdf_files.len(), ..Default::default() };
+
+        let current: BTreeSet<&String> = pdf_files.iter().map(|(_, file)| file).collect();
+        let gone: Vec<String> = self.files.keys().filter(|f| !current.contains(f)).cloned().collect();
+        let mut stale = BTreeSet::new();
+        for file in gone {
+            if let Some(entry) = self.files.remove(&file) {
+                stale.insert(entry.id);
+                stats.removed += 1;
+            }
+        }
+
+        let changed: Vec<(&String, (u64, u64, u32))> = pdf_files.iter()
+            .filter_map(|(_, file)| file_state(Path::new(file)).ok().map(|state| (file, state)))
+            .filter(|(file, (size, secs, nanos))| {
+                self.files.get(*file).map_or(true, |e| (e.size, e.mtime_secs, e.mtime_nanos) != (*size, *secs, *nanos))
+            })
+            .collect();
+
+        let extracted: Vec<_> = changed.par_iter().map(|(file, state)| {
+            let pages = pdf_tools::extract_checked(Path::new(file), settings).and_then(|content| {
+                let text = String::from_utf8_lossy(&content);
+                let pages = settings.tools.split_pages(&text)?
+                    .iter()
+                    .map(|page| {
+                        let page = normalize::normalize_page(page, settings);
+                        tokenize(&normalize::fold_text(&page, &settings.fold_options).text).collect::<BTreeSet<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                Ok((content, pages))
+            });
+            (*file, *state, pages)
+        }).collect();
+
+        // the old postings of a changed file are stale either way
+        for (file, _, _) in &extracted {
+            if let Some(entry) = self.files.remove(*file) {
+                stale.insert(entry.id);
+            }
+        }
+        self.remove_files(&stale);
+
+        let mut skipped = vec![];
+        for (file, (size, mtime_secs, mtime_nanos), pages) in extracted {
+            let (content, pages) = match pages {
+                Ok(pages) => pages,
+                Err(e) => {
+                    match e.downcast_ref::<Skipped>() {
+                        Some(skip) => {
+                            skipped.push((file.clone(), skip.to_string()));
+                            stats.skipped += 1;
+                        },
+                        None => {
+                            pdf_tools::report_error(Path::new(file), &e);
+                            stats.failed += 1;
+                        },
+                    }
+                    continue;
+                }
+            };
+            let id = self.next_id;
+            self.next_id += 1;
+            let text_path = self.text_path(id);
+            if let Some(folder) = text_path.parent() {
+                fs::create_dir_all(folder)?;
+            }
+            fs::write(text_path, &content)?;
+            self.add_pages(id, &pages);
+            self.files.insert(file.clone(), IndexedFile { id, size, mtime_secs, mtime_nanos, pages: pages.len() });
+            stats.updated += 1;
+        }
+        for postings in self.terms.values_mut() {
+            postings.sort();
+            postings.dedup();
+        }
+        pdf_tools::report_skipped(&skipped);
+
+        Ok(stats)
+    }
+
+    /// Pages containing every word of the query, grouped per file.
+    pub fn lookup(&self, query: &str) -> BTreeMap<String, Vec<usize>> {
+        let mut result: Option<BTreeSet<Posting>> = None;
+        for word in tokenize(query) {
+            let postings: BTreeSet<Posting> = self.terms.get(&word).map(|p| p.iter().copied().collect()).unwrap_or_default();
+            result = Some(match result {
+                Some(found) => found.intersection(&postings).copied().collect(),
+                None => postings,
+            });
+        }
+
+        let names: BTreeMap<u32, &String> = self.files.iter().map(|(name, entry)| (entry.id, name)).collect();
+        let mut pages: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for posting in result.unwrap_or_default() {
+            if let Some(name) = names.get(&posting.file) {
+                pages.entry(name.to_string()).or_default().push(posting.page as usize);
+            }
+        }
+        pages
+    }
+}
+
+/// Prints the indexed hits with the standard printer, only the pages found in the index are searched.
+/// The text comes from the index, a file that can't be read is reported and left out.
+pub fn search_index(index: &PdfIndex, query: &str, settings: &Settings) -> Result<(), BoxError> {
+    let query = normalize::fold_text(query, &settings.fold_options).text;
+    let words: Vec<String> = tokenize(&query).map(|w| regex::escape(&w)).collect();
+    if words.is_empty() {
+        return Err("The query contains no words".into());
+    }
+    let inner = RegexMatcher::new(&format!(r"(?i)\b({})\b", words.join("|")))?;
+    let matcher = FoldingMatcher { inner, options: settings.fold_options };
+
+    for (file, pages) in index.lookup(&query) {
+        let content = match index.read_text(&file, settings) {
+            Ok(content) => content,
+            Err(e) => {
+                pdf_tools::report_error(Path::new(&file), &e);
+                continue;
+            }
+        };
+        let text = String::from_utf8_lossy(&content);
+        let page_texts = settings.tools.split_pages(&text)?;
+
+        let mut printer = settings.create_printer();
+        let p = printer.get_mut();
+        p.set_color(&settings.info_color_spec)?;
+        p.write_all(format!("Searching: {}\n", file).as_bytes())?;
+
+        let mut total = 0;
+        for page in pages {
+            if let Some(page_text) = page_texts.get(page) {
+                printer = settings.create_page_printer(printer.into_inner(), page);
+                let page_text = normalize::normalize_page(page_text, settings);
+                let folded = FoldedPage::new(&page_text, &settings.fold_options);
+                match grep_utils::search_pdf_page(&folded.matcher(&matcher), &mut printer, &PdfPage::new(page_text.as_bytes(), page), settings) {
+                    Ok(count) => total += count,
+                    Err(e) => eprint!("{}", e),
+                }
+            }
+        }
+
+        let p = printer.get_mut();
+        p.set_color(&settings.info_color_spec)?;
+        p.write_all(format!("End of file: found {} matches.\n\n", total).as_bytes())?;
+        p.reset()?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_index_lookup() {
+    let folder = std::env::temp_dir().join(format!("pdf_grep_index_test_{}", std::process::id()));
+    let mut index = PdfIndex { folder: folder.clone(), ..Default::default() };
+    let doc_a = ["Quantum field theory", "A theory of everything"].map(|p| tokenize(p).collect::<BTreeSet<_>>());
+    let doc_b = ["no match here", "Field Theory"].map(|p| tokenize(p).collect::<BTreeSet<_>>());
+    index.add_pages(0, &doc_a);
+    index.files.insert("a.pdf".into(), IndexedFile { id: 0, size: 0, mtime_secs: 0, mtime_nanos: 0, pages: 2 });
+    index.add_pages(1, &doc_b);
+    index.files.insert("b.pdf".into(), IndexedFile { id: 1, size: 0, mtime_secs: 0, mtime_nanos: 0, pages: 2 });
+
+    let found = index.lookup("field THEORY");
+    assert_eq!(found.get("a.pdf"), Some(&vec![0]));
+    assert_eq!(found.get("b.pdf"), Some(&vec![1]));
+
+    let text_path = index.text_path(1);
+    fs::create_dir_all(text_path.parent().unwrap()).unwrap();
+    fs::write(&text_path, "no match here\x0cField Theory").unwrap();
+    index.remove_files(&BTreeSet::from([1]));
+    assert!(!index.lookup("field theory").contains_key("b.pdf"));
+    assert!(!text_path.exists());
+    fs::remove_dir_all(&folder).unwrap();
+}
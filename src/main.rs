@@ -3,15 +3,21 @@
 
 //use std::collections::BTreeMap;
 use std::io::{self, Write};
-use std::path::{Path, PathBuf};
+use std::path::Path;
+use std::time::Instant;
 
 use std::env;
 
 use std::result::Result;
 
 // mod xpdf_tools;
+mod fuzzy;
 mod grep_utils;
 // mod grep_utils2;
+mod index;
+mod normalize;
+mod patterns;
+mod query;
 mod utils;
 mod settings;
 mod pdf_tools;
@@ -19,16 +25,17 @@ mod pdf_tools;
 // mod parse_utils;
 // mod types;
 
-use pdf_tools::XpdfWrapper;
 
 
-use xpdf_tools::types::XpdfArgs;
 //use xpdf_tools::xpdf_info::PdfInfo;
-use xpdf_tools::XpdfTools;
+use grep::printer::SummaryKind;
 use xpdf_tools::{self};
 
-use crate::pdf_tools::{AvailablePdfTools, PdfDummyTool};
-use crate::settings::{Actions, Settings, ShortenLineMode};
+use crate::pdf_tools::{FileResult, TextCache};
+use crate::index::PdfIndex;
+use crate::patterns::PatternSet;
+use crate::query::Query;
+use crate::settings::{Actions, CacheAction, IndexAction, OutputMode, Settings, ShortenLineMode};
 
 type BoxError = std::boxed::Box<dyn
 	std::error::Error   // must implement Error to satisfy ?
@@ -42,22 +49,36 @@ fn _write_to_file(file_path: &str, content: &String) {
     file.write_all(content.as_bytes()).unwrap();
 }
 
-fn main() -> Result<(), BoxError>{
+/// Exit status like grep: 0 if something matched, 1 if nothing matched, 2 on errors.
+fn main() {
+    let status = run().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        2
+    });
+    std::process::exit(status);
+}
+
+fn run() -> Result<i32, BoxError> {
     env::set_var("RUST_BACKTRACE", "1");
-    println!("{}", xpdf_tools::get_version());
 
     //let mut settings = Settings::default();
     let mut settings = Settings::new();
+    if settings.verbose {
+        eprintln!("{}", xpdf_tools::get_version());
+    }
+    if let Some(threads) = settings.cli.as_ref().and_then(|cli| cli.threads) {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()?;
+    }
     settings.shorten_line_mode = ShortenLineMode::Trim(25);
-    settings.tools = match settings.use_pdf_tool {
-        AvailablePdfTools::UseXpdfTools => {
-            let t = XpdfTools::builder(PathBuf::from(settings.xpdf_tools_folder.as_ref().unwrap())).unwrap()
-                    .extra_args(vec![XpdfArgs::Encoding("UTF-8".into())])
-                    .build();
-            Box::new(XpdfWrapper {tools: t})
-        },
-        AvailablePdfTools::UsePdfDummyTool => Box::new(PdfDummyTool {}),
-    };
+    settings.tools = pdf_tools::resolve_tools(&settings)?;
+    if let Some(Actions::Search {pattern, regexp, pattern_file, query, ..}) = settings.cli.as_ref().map(|cli| &cli.command) {
+        if *query {
+            settings.query = Some(Query::parse(pattern.as_deref().unwrap_or_default(), &settings)?);
+        } else {
+            let patterns = patterns::collect_patterns(pattern.as_ref(), regexp, pattern_file)?;
+            settings.patterns = Some(PatternSet::new(patterns, &settings)?);
+        }
+    }
     // let _tools = XpdfTools::builder(PathBuf::from("./tools/xpdf-tools-win-4.05/bin64/")).unwrap()
     //                 .extra_args(vec![XpdfArgs::Encoding("UTF-8".into())])
     //                 .build();    
@@ -65,46 +86,79 @@ fn main() -> Result<(), BoxError>{
     // settings.tools = Box::new(XpdfWrapper {tools: _tools});
     // let cli = Cli::parse();
     let cli = settings.cli.as_ref().unwrap();
+    let mut status = 0;
     match cli.command { 
-        Actions::Info => { 
+        Actions::Info {ref columns, ref sort} => { 
             if let Some(dir) = cli.directory.as_deref() {
-                pdf_tools::get_info_dir(dir);
+                pdf_tools::get_info_dir(dir, columns, sort, &settings)?;
             } else if let Some(file) = cli.file.as_deref() {
                 pdf_tools::get_info_file(file, &settings);
             }
         },
         Actions::Test => { println!("Action: test");},
-        Actions::Search {ref pattern}=> {
+        Actions::Search {..}=> {
+            let start = Instant::now();
+            let mut counts = vec![];
             if let Some(dir) = cli.directory.as_deref() {
-                pdf_tools::search_dir(dir, pattern, &settings)?;
+                counts = pdf_tools::search_dir(dir, &settings)?;
             } else if let Some(file) = cli.file.as_deref() {
                 // pdf_tools::search_file(&file, pattern, &settings);
-                settings.tools.search_file(file, pattern, &settings)?;
+                let stdout = settings.create_buffer_writer();
+                let mut buffer = stdout.buffer();
+                counts.push(pdf_tools::search_one(file, &settings, &mut buffer));
+                stdout.print(&buffer)?;
             } 
+            if settings.output_mode == OutputMode::Json && settings.summary_kind.is_none() {
+                grep_utils::write_json_summary(&counts.iter().filter_map(|result| result.matches()).collect::<Vec<_>>(), start.elapsed())?;
+            }
+            // -L succeeds when it lists a file
+            let found = match settings.summary_kind {
+                Some(SummaryKind::PathWithoutMatch) => counts.contains(&FileResult::Matches(0)),
+                _ => counts.iter().filter_map(|result| result.matches()).any(|count| count > 0),
+            };
+            // skipped files are only listed, they don't make the search fail
+            let failed = counts.contains(&FileResult::Failed);
+            // -q only has to know about one match, the errors of other files don't matter then
+            status = if failed && !(found && settings.summary_kind == Some(SummaryKind::Quiet)) {
+                2
+            } else if found {
+                0
+            } else {
+                1
+            };
             //println!("Action: search:{}", *pattern);
         },
-        Actions::Text => {
+        Actions::Text {..} => {
             if let Some(_dir) = cli.directory.as_deref() {
                 println!("No implemented");
             } else if let Some(file) = cli.file.as_deref() {
-                let tools = XpdfTools::builder(PathBuf::from("./tools/xpdf-tools-win-4.05/bin64/")).unwrap()
-                    .extra_args(vec![XpdfArgs::Simple])
-                    .build();
-                match tools.pdf_text_as_string(file) {
+                match settings.tools.pdf_text_pages(file, settings.page_ranges.as_ref()) {
                     Ok(content) => {
-                        println!("{:?}", content);
-                        //io::stdout().write_all(content.as_bytes()).unwrap();
+                        let text = String::from_utf8_lossy(&content);
+                        let mut stdout = io::stdout().lock();
+                        let mut pages = settings.tools.split_pages(&text)?;
+                        // the text after the form feed of the last page is not a page
+                        if pages.last().is_some_and(|page| page.is_empty()) {
+                            pages.pop();
+                        }
+                        for (page, page_text) in pages.iter().enumerate() {
+                            // blank pages keep their form feed, so the page numbers stay the same
+                            if settings.page_selected(page) {
+                                stdout.write_all(page_text.as_bytes())?;
+                                stdout.write_all(b"\x0c")?;
+                            }
+                        }
+                        stdout.flush()?;
                     },
                     Err(e) => {
-                        io::stderr().write_all(e.message.as_bytes()).unwrap();
+                        io::stderr().write_all(e.to_string().as_bytes()).unwrap();
                         io::stderr().write_all(b"\n").unwrap();
-                        io::stderr().write_all(e.process_message.as_bytes()).unwrap();
                         io::stderr().flush().unwrap();
                     },
                 }
             } 
         },
-        Actions::Rand {ref length} => {
+        Actions::Rand {ref length, ..} => {
             let mut snippet_length = 150;
             if let Some(l) = length {
                 snippet_length = *l;
@@ -114,13 +168,52 @@ fn main() -> Result<(), BoxError>{
                 println!("{:?}", result.0);
                 println!("{}", result.1);
             }
-        }
+        },
+        Actions::Cache {ref action} => {
+            let cache = TextCache::open()?;
+            match action {
+                CacheAction::Stats => {
+                    let stats = cache.stats()?;
+                    println!("Cache folder: {}", cache.folder.display());
+                    println!("Cached files: {} ({} deleted)", stats.entries, stats.missing_files);
+                    println!("Cached text: {} bytes", stats.text_bytes);
+                },
+                CacheAction::Prune => {
+                    let removed = cache.prune()?;
+                    println!("Removed {} entries of deleted files", removed);
+                },
+                CacheAction::Clear => {
+                    cache.clear()?;
+                    println!("Cache cleared: {}", cache.folder.display());
+                },
+            }
+        },
+        Actions::Index {ref action} => {
+            let dir = cli.directory.as_deref().ok_or("The index needs a directory (-d)")?;
+            let mut index = PdfIndex::load(dir)?;
+            match action {
+                IndexAction::Build => {
+                    let stats = index.update(&settings)?;
+                    let index_path = index.save()?;
+                    println!("Indexed {} files ({} updated, {} removed, {} failed, {} skipped) in {}",
+                        stats.files, stats.updated, stats.removed, stats.failed, stats.skipped, index_path.display());
+                },
+                IndexAction::Search {ref query} => {
+                    if index.files.is_empty() {
+                        return Err(format!("No index for {}, run 'index build' first", dir.display()).into());
+                    }
+                    index::search_index(&index, query, &settings)?;
+                },
+            }
+        },
 
     }
-    println!("{:?}", cli);
+    if settings.verbose {
+        eprintln!("{:?}", cli);
+    }
 
     
-   Ok(())
+   Ok(status)
     
 }
 
@@ -175,6 +268,9 @@ fn normalize_whitespace() {
 #[ignore]
 #[test]
 fn test_encoding() {
+    use std::path::PathBuf;
+    use xpdf_tools::{types::XpdfArgs, XpdfTools};
+
     let tools = XpdfTools::builder(PathBuf::from("./tools/xpdf-tools-win-4.05/bin64/")).unwrap()
         .extra_args(vec![XpdfArgs::Encoding("UTF-8".into())])
         .build();
@@ -209,8 +305,11 @@ fn test_encoding() {
 #[ignore]
 #[test]
 fn test_folder_mode() {
+    use std::path::PathBuf;
     use utils::get_folder_files;
+    use pdf_tools::XpdfWrapper;
     use settings::FolderSearchMode;
+    use xpdf_tools::{types::XpdfArgs, XpdfTools};
     use settings::ShortenLineMode;
     use std::time::Instant;
     use termcolor::ColorChoice;
@@ -223,7 +322,7 @@ fn test_folder_mode() {
             .extra_args(vec![XpdfArgs::Encoding("UTF-8".into())])
             .build();
 
-    _settings.tools = Box::new(XpdfWrapper{tools: _tools});
+    _settings.tools = Box::new(XpdfWrapper{tools: _tools, tools_folder: PathBuf::from(tools_folder), timeout: None});
     //_settings.tools = Box::new(settings::PdfExtractWrapper{});
     _settings.folder_search_mode = FolderSearchMode::ThreadPerFile;
     _settings.color_choice = ColorChoice::Auto;
@@ -231,7 +330,7 @@ fn test_folder_mode() {
     
     //let folder = "c:\\Data\\Library\\Books";
     let folder = "./data/Math";
-    let _file_list = get_folder_files(Path::new(folder));
+    let _file_list = get_folder_files(Path::new(folder), &_settings.walk).unwrap();
     // let pdf_map = get_folder_tree(Path::new(folder));
 
     // let _file_list:Vec<_> = pdf_map.iter()
@@ -248,6 +347,7 @@ fn test_folder_mode() {
 
     let mut start = Instant::now();
     let pattern = "theory".to_string();
+    _settings.patterns = Some(PatternSet::new(vec![pattern], &_settings).unwrap());
     // pdf_map.par_iter().for_each(|(dir, list)| {
     //     //search_invoke(dir, list, pattern, &tools);
     //     search_invoke_folders(dir, list, &pattern, &_settings);
@@ -257,9 +357,12 @@ fn test_folder_mode() {
     
 
     start = Instant::now();
+    let stdout = _settings.create_buffer_writer();
     _file_list.par_iter().for_each(|(_dir, file)| {
         //pdf_tools::search_invoke_file(String::from(file), &pattern, &_settings);
-       _settings.tools.search_file(Path::new(&file), &pattern, &_settings).unwrap();
+       let mut buffer = stdout.buffer();
+       _settings.tools.search_file(Path::new(&file), &_settings, &mut buffer).unwrap();
+       stdout.print(&buffer).unwrap();
     });
 
     let elapsed2 = start.elapsed();
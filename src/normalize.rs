@@ -0,0 +1,328 @@
+use std::borrow::Cow;
+
+use grep::{matcher::{Match, Matcher, NoCaptures}, regex::RegexMatcher};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::settings::Settings;
+
+/// Text of a page as it is searched, after the enabled normalization steps.
+/// Unicode folding is not applied here but by `FoldedPage`, so the printed text stays the original.
+pub fn normalize_page<'a>(text: &'a str, settings: &Settings) -> Cow<'a, str> {
+    if settings.dehyphenate {
+        dehyphenate(text)
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Page text followed by the first `lines` lines of the next page, normalized together so a word
+/// broken over the page end is rejoined. The second value is the byte offset where the next page starts.
+pub fn page_with_overlap<'a>(page: &'a str, next: Option<&str>, lines: usize, settings: &Settings) -> (Cow<'a, str>, Option<usize>) {
+    let overlap: Vec<&str> = next.map_or(vec![], |next| next.split_inclusive('\n').take(lines).collect());
+    if overlap.iter().all(|line| line.trim().is_empty()) {
+        return (normalize_page(page, settings), None);
+    }
+
+    let mut text = page.to_string();
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    let page_lines = text.matches('\n').count();
+    text.extend(overlap);
+
+    // the normalization keeps the number of lines, so the next page starts after the same line
+    let text = normalize_page(&text, settings).into_owned();
+    let boundary = if page_lines == 0 {
+        0
+    } else {
+        text.match_indices('\n').nth(page_lines - 1).map_or(text.len(), |(pos, _)| pos + 1)
+    };
+    (Cow::Owned(text), Some(boundary))
+}
+
+/// Rejoins words broken with a hyphen at a line end ("distri-\nbution").
+/// The rest of the word is moved up to the line with the hyphen, so the number of lines and
+/// with it every reported line number stays the same as in the extracted text.
+/// The hyphen is kept for compounds: when the word goes on with an uppercase letter or a digit
+/// ("Jean-\nPaul", "COVID-\n19"), or when the first part has a hyphen of its own ("state-of-the-\nart").
+/// A number before the hyphen is left as it is, "10-\n20" is a range.
+pub fn dehyphenate(text: &str) -> Cow<'_, str> {
+    if !text.contains("-\n") {
+        return Cow::Borrowed(text);
+    }
+
+    let mut lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+    for i in 0..lines.len() {
+        let mut next = i + 1;
+        // the tail moved up can end in a hyphen again: "distri-\nbu-\ntion"
+        while next < lines.len() {
+            let Some((line, rest)) = join_hyphenated(&lines[i], &lines[next]) else {
+                break;
+            };
+            lines[i] = line;
+            lines[next] = rest;
+            if !lines[next].is_empty() {
+                break;
+            }
+            next += 1;
+        }
+    }
+
+    Cow::Owned(lines.join("\n"))
+}
+
+/// The line with the start of the next line moved up, and what is left of the next line.
+/// None when the line does not end in a hyphen that breaks a word.
+fn join_hyphenated(line: &str, next: &str) -> Option<(String, String)> {
+    let head = line.strip_suffix('-')?;
+    let word_start = head.rfind(|c: char| c.is_whitespace()).map_or(0, |pos| pos + 1);
+    let first_part = &head[word_start..];
+    let tail_len = next.find(|c: char| c.is_whitespace()).unwrap_or(next.len());
+    let tail = &next[..tail_len];
+
+    let joins = first_part.chars().last().is_some_and(|c| c.is_alphabetic())
+        && tail.chars().next().is_some_and(|c| c.is_alphanumeric());
+    if !joins {
+        return None;
+    }
+
+    let compound = first_part.contains('-')
+        || tail.chars().next().is_some_and(|c| c.is_uppercase() || c.is_numeric());
+    let joined = format!("{}{}", if compound { line } else { head }, tail);
+    Some((joined, next[tail_len..].trim_start().to_string()))
+}
+
+/// Unicode folding steps applied to the page text and the pattern alike.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FoldOptions {
+    /// NFKC normalization, also expands the ligatures of the `U+FB00` block
+    pub nfkc: bool,
+    /// Expands ligatures like "œ" and "æ" that NFKC keeps
+    pub ligatures: bool,
+    /// Drops accents and other combining marks, "naïve" matches "naive"
+    pub accents: bool,
+    /// Unifies curly quotes and the different dashes to their ASCII form
+    pub punctuation: bool,
+}
+
+impl Default for FoldOptions {
+    fn default() -> Self {
+        FoldOptions { nfkc: true, ligatures: true, accents: false, punctuation: true }
+    }
+}
+
+impl FoldOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.nfkc || self.ligatures || self.accents || self.punctuation
+    }
+}
+
+fn expand_ligature(c: char) -> Option<&'static str> {
+    let expanded = match c {
+        '\u{fb00}' => "ff",
+        '\u{fb01}' => "fi",
+        '\u{fb02}' => "fl",
+        '\u{fb03}' => "ffi",
+        '\u{fb04}' => "ffl",
+        '\u{fb05}' | '\u{fb06}' => "st",
+        '\u{152}' => "OE",
+        '\u{153}' => "oe",
+        '\u{c6}' => "AE",
+        '\u{e6}' => "ae",
+        '\u{132}' => "IJ",
+        '\u{133}' => "ij",
+        _ => return None,
+    };
+    Some(expanded)
+}
+
+fn fold_punctuation(c: char) -> char {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' | '\u{2032}' => '\'',
+        '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{2033}' | '\u{ab}' | '\u{bb}' => '"',
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}' | '\u{2212}' | '\u{ad}' => '-',
+        c => c,
+    }
+}
+
+/// Folded text with the byte range in the original text of every folded byte.
+#[derive(Debug)]
+pub struct FoldedText {
+    pub text: String,
+    spans: Vec<(usize, usize)>,
+    original_len: usize,
+}
+
+impl FoldedText {
+    /// First folded byte that comes from the original text at or after `at`.
+    fn folded_offset(&self, at: usize) -> usize {
+        self.spans.partition_point(|(start, _)| *start < at)
+    }
+
+    /// Range in the original text covering all characters the folded match was made from.
+    fn original_match(&self, m: Match) -> Match {
+        let start = self.spans.get(m.start()).map_or(self.original_len, |(start, _)| *start);
+        let end = if m.is_empty() { start } else { self.spans[m.end() - 1].1 };
+        Match::new(start, end)
+    }
+}
+
+/// Applies the fold steps per character together with its combining marks, so NFD accents
+/// are folded as one unit and every folded byte still knows where it came from.
+pub fn fold_text(text: &str, options: &FoldOptions) -> FoldedText {
+    let mut folded = FoldedText { text: String::with_capacity(text.len()), spans: Vec::with_capacity(text.len()), original_len: text.len() };
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        while let Some((pos, mark)) = chars.peek().copied().filter(|(_, mark)| is_combining_mark(*mark)) {
+            end = pos + mark.len_utf8();
+            chars.next();
+        }
+
+        let unit = &text[start..end];
+        let mut result = if options.nfkc { unit.nfkc().collect::<String>() } else { unit.to_string() };
+        if options.ligatures {
+            result = result.chars().fold(String::new(), |mut s, c| {
+                match expand_ligature(c) {
+                    Some(expanded) => s.push_str(expanded),
+                    None => s.push(c),
+                }
+                s
+            });
+        }
+        if options.accents {
+            result = result.nfd().filter(|c| !is_combining_mark(*c)).collect();
+        }
+        if options.punctuation {
+            result = result.chars().map(fold_punctuation).collect();
+        }
+
+        folded.text.push_str(&result);
+        folded.spans.resize(folded.spans.len() + result.len(), (start, end));
+    }
+    folded
+}
+
+/// Matcher of folded patterns, `FoldedPage::matcher` runs it over text that is folded already.
+pub trait FoldedMatcher: Matcher {
+    fn find_folded(&self, folded: &[u8], at: usize) -> Result<Option<Match>, Self::Error>;
+}
+
+/// Matches the folded pattern against the folded haystack and reports the match in the
+/// original haystack, so the printers highlight the text as it was extracted. The haystack is
+/// folded on every call, a page is searched through `FoldedPage` to fold it only once.
+#[derive(Debug, Clone)]
+pub struct FoldingMatcher<M = RegexMatcher> {
+    pub inner: M,
+    pub options: FoldOptions,
+}
+
+impl<M: Matcher> Matcher for FoldingMatcher<M> {
+    type Captures = NoCaptures;
+    type Error = M::Error;
+
+    fn find_at(&self, haystack: &[u8], at: usize) -> Result<Option<Match>, M::Error> {
+        // pure ASCII text is the same after folding
+        let text = match std::str::from_utf8(haystack) {
+            Ok(text) if self.options.is_enabled() && !text.is_ascii() => text,
+            _ => return self.inner.find_at(haystack, at),
+        };
+        let folded = fold_text(text, &self.options);
+        let found = self.inner.find_at(folded.text.as_bytes(), folded.folded_offset(at))?;
+        Ok(found.map(|m| folded.original_match(m)))
+    }
+
+    fn new_captures(&self) -> Result<NoCaptures, M::Error> {
+        Ok(NoCaptures::new())
+    }
+}
+
+impl<M: Matcher> FoldedMatcher for FoldingMatcher<M> {
+    fn find_folded(&self, folded: &[u8], at: usize) -> Result<Option<Match>, M::Error> {
+        self.inner.find_at(folded, at)
+    }
+}
+
+/// Page text folded once before it is searched, `None` when folding leaves it as it is.
+pub struct FoldedPage<'a> {
+    page: &'a [u8],
+    folded: Option<FoldedText>,
+}
+
+impl<'a> FoldedPage<'a> {
+    pub fn new(page: &'a str, options: &FoldOptions) -> FoldedPage<'a> {
+        // pure ASCII text is the same after folding
+        let folded = (options.is_enabled() && !page.is_ascii()).then(|| fold_text(page, options));
+        FoldedPage { page: page.as_bytes(), folded }
+    }
+
+    /// `matcher` searching this page, for the searcher and the printers.
+    pub fn matcher<'m, M: FoldedMatcher>(&'m self, matcher: &'m M) -> PageMatcher<'m, M> {
+        PageMatcher { matcher, page: self }
+    }
+}
+
+/// Searches the folded page for the haystacks that are part of the page, as the ones the searcher
+/// and the printers pass for a slice, and maps the matches back to the original text.
+pub struct PageMatcher<'a, M> {
+    matcher: &'a M,
+    page: &'a FoldedPage<'a>,
+}
+
+impl<'a, M: FoldedMatcher> Matcher for PageMatcher<'a, M> {
+    type Captures = NoCaptures;
+    type Error = M::Error;
+
+    fn find_at(&self, haystack: &[u8], at: usize) -> Result<Option<Match>, M::Error> {
+        let Some(folded) = &self.page.folded else {
+            return self.matcher.find_folded(haystack, at);
+        };
+        // where the haystack starts in the page, any other haystack is folded on its own
+        let start = (haystack.as_ptr() as usize).wrapping_sub(self.page.page.as_ptr() as usize);
+        let in_page = matches!(start.checked_add(haystack.len()), Some(end) if end <= self.page.page.len());
+        if !in_page {
+            return self.matcher.find_at(haystack, at);
+        }
+
+        let folded_start = folded.folded_offset(start);
+        let folded_end = folded.folded_offset(start + haystack.len());
+        let window = &folded.text.as_bytes()[folded_start..folded_end];
+        let found = self.matcher.find_folded(window, folded.folded_offset(start + at) - folded_start)?;
+        Ok(found.map(|m| {
+            let m = folded.original_match(Match::new(folded_start + m.start(), folded_start + m.end()));
+            Match::new(m.start() - start, (m.end() - start).min(haystack.len()))
+        }))
+    }
+
+    fn new_captures(&self) -> Result<NoCaptures, M::Error> {
+        Ok(NoCaptures::new())
+    }
+}
+
+#[test]
+fn test_dehyphenate() {
+    assert_eq!(dehyphenate("the distri-\nbution of mass"), "the distribution\nof mass");
+    assert_eq!(dehyphenate("Jean-\nPaul Sartre"), "Jean-Paul\nSartre");
+    assert_eq!(dehyphenate("the COVID-\n19 pandemic"), "the COVID-19\npandemic");
+    assert_eq!(dehyphenate("state-of-the-\nart methods"), "state-of-the-art\nmethods");
+    assert_eq!(dehyphenate("the distri-\nbu-\ntion of mass"), "the distribution\n\nof mass");
+    assert_eq!(dehyphenate("pages 10-\n20 and a list:\n-\nitem"), "pages 10-\n20 and a list:\n-\nitem");
+}
+
+#[test]
+fn test_folding_matcher() {
+    let options = FoldOptions { accents: true, ..Default::default() };
+    let pattern = fold_text("naïve", &options).text;
+    let matcher = FoldingMatcher { inner: RegexMatcher::new(&format!("{}|efficient|don't", pattern)).unwrap(), options };
+
+    let text = "a nai\u{308}ve,\ne\u{fb03}cient don\u{2019}t";
+    let mut found = vec![];
+    matcher.find_iter(text.as_bytes(), |m| { found.push(&text[m]); true }).unwrap();
+    assert_eq!(found, ["nai\u{308}ve", "e\u{fb03}cient", "don\u{2019}t"]);
+
+    // the second line as the searcher passes it, found in the page folded once
+    let page = FoldedPage::new(text, &options);
+    let line = &text.as_bytes()[text.find('\n').unwrap() + 1..];
+    let m = page.matcher(&matcher).find_at(line, 1).unwrap().unwrap();
+    assert_eq!(&line[m], "don\u{2019}t".as_bytes());
+}
@@ -0,0 +1,129 @@
+use std::path::{Path, PathBuf};
+
+use grep::matcher::{Match, Matcher, NoCaptures, NoError};
+
+use crate::{fuzzy::FuzzyMatcher, normalize::{self, FoldedMatcher, FoldingMatcher}, settings::Settings, BoxError};
+
+/// Reads a pattern file, one pattern per line. Empty lines are skipped, they would match every line.
+pub fn read_pattern_file(file_path: &Path) -> Result<Vec<String>, BoxError> {
+    let content = std::fs::read_to_string(file_path)
+        .map_err(|e| format!("Can't read the pattern file {}: {}", file_path.display(), e))?;
+    Ok(content.lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// All patterns of a search in the order given: the pattern argument, then `-e`, then the lines of each `-f` file.
+pub fn collect_patterns(pattern: Option<&String>, regexp: &[String], pattern_files: &[PathBuf]) -> Result<Vec<String>, BoxError> {
+    let mut patterns: Vec<String> = pattern.into_iter().chain(regexp).cloned().collect();
+    for file in pattern_files {
+        patterns.extend(read_pattern_file(file)?);
+    }
+    if patterns.is_empty() {
+        return Err("No pattern to search for".into());
+    }
+    Ok(patterns)
+}
+
+/// The regex matcher of the patterns, or the approximate one of `--fuzzy`.
+#[derive(Debug, Clone)]
+pub enum PatternMatcher {
+    Regex(FoldingMatcher),
+    Fuzzy(FoldingMatcher<FuzzyMatcher>),
+}
+
+impl Matcher for PatternMatcher {
+    type Captures = NoCaptures;
+    type Error = NoError;
+
+    fn find_at(&self, haystack: &[u8], at: usize) -> Result<Option<Match>, NoError> {
+        match self {
+            PatternMatcher::Regex(matcher) => matcher.find_at(haystack, at),
+            PatternMatcher::Fuzzy(matcher) => matcher.find_at(haystack, at),
+        }
+    }
+
+    fn new_captures(&self) -> Result<NoCaptures, NoError> {
+        Ok(NoCaptures::new())
+    }
+}
+
+impl FoldedMatcher for PatternMatcher {
+    fn find_folded(&self, folded: &[u8], at: usize) -> Result<Option<Match>, NoError> {
+        match self {
+            PatternMatcher::Regex(matcher) => matcher.find_folded(folded, at),
+            PatternMatcher::Fuzzy(matcher) => matcher.find_folded(folded, at),
+        }
+    }
+}
+
+/// The patterns of a search compiled into one matcher, so every page is searched once.
+/// A matcher per pattern is only used to tell which pattern found a match, see `Search --tag`.
+#[derive(Debug)]
+pub struct PatternSet {
+    pub patterns: Vec<String>,
+    pub matcher: PatternMatcher,
+    tag_matchers: Vec<PatternMatcher>,
+    /// Name the pattern of each match, `--tag`
+    pub tags: bool,
+}
+
+impl PatternSet {
+    pub fn new(patterns: Vec<String>, settings: &Settings) -> Result<PatternSet, BoxError> {
+        let (matcher, tag_matchers) = match settings.fuzzy {
+            Some(distance) => (
+                PatternMatcher::Fuzzy(settings.create_fuzzy_matcher_many(&patterns, distance)?),
+                patterns.chunks(1)
+                    .map(|pattern| settings.create_fuzzy_matcher_many(pattern, distance).map(PatternMatcher::Fuzzy))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => (
+                PatternMatcher::Regex(settings.create_matcher_many(&patterns)?),
+                patterns.iter()
+                    .map(|pattern| settings.create_matcher(pattern).map(PatternMatcher::Regex))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+        Ok(PatternSet { patterns, matcher, tag_matchers, tags: settings.tag_patterns })
+    }
+
+    /// The first pattern with a match at the start of `m`, the one the alternation of all patterns reports.
+    pub fn tag(&self, haystack: &[u8], m: Match) -> Option<&str> {
+        self.tag_matchers.iter().position(|matcher| {
+            matches!(matcher.find_at(haystack, m.start()), Ok(Some(found)) if found.start() == m.start())
+        }).map(|index| self.patterns[index].as_str())
+    }
+
+    /// Edit distance between the match and the closest pattern, with `--fuzzy`.
+    pub fn distance(&self, haystack: &[u8], m: Match) -> Option<usize> {
+        let PatternMatcher::Fuzzy(matcher) = &self.matcher else {
+            return None;
+        };
+        let text = String::from_utf8_lossy(&haystack[m]);
+        Some(matcher.inner.edit_distance(&normalize::fold_text(&text, &matcher.options).text))
+    }
+
+    pub fn has_labels(&self) -> bool {
+        self.tags || matches!(self.matcher, PatternMatcher::Fuzzy(_))
+    }
+
+    /// "aspirin", "~1" or "aspirin ~1" for the output.
+    pub fn label(&self, haystack: &[u8], m: Match) -> String {
+        let tag = self.tag(haystack, m).filter(|_| self.tags);
+        let distance = self.distance(haystack, m).map(|distance| format!("~{}", distance));
+        tag.map(str::to_string).into_iter().chain(distance).collect::<Vec<_>>().join(" ")
+    }
+}
+
+#[test]
+fn test_pattern_set() {
+    let settings = Settings::default();
+    let set = PatternSet::new(vec!["aspirin".into(), r"ibu\w+".into(), "IBUPROFEN 400".into()], &settings).unwrap();
+    let text = b"Aspirin or ibuprofen 400 mg";
+
+    let mut tags = vec![];
+    set.matcher.find_iter(text, |m| { tags.push((&text[m], set.tag(text, m))); true }).unwrap();
+    assert_eq!(tags, [(&b"Aspirin"[..], Some("aspirin")), (&b"ibuprofen"[..], Some(r"ibu\w+"))]);
+}
@@ -0,0 +1,245 @@
+use std::{collections::BTreeMap, fs, io::Read, path::{Path, PathBuf}, time::UNIX_EPOCH};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::{utils::{self, PageRanges, FNV_OFFSET}, BoxError};
+
+use super::PDFTools;
+
+const CACHE_FOLDER_NAME: &str = "pdf_grep";
+const TEXT_FOLDER_NAME: &str = "texts";
+
+fn content_hash(file_path: &Path) -> Result<String, BoxError> {
+    let mut file = fs::File::open(file_path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut hash = FNV_OFFSET;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hash = utils::fnv1a(hash, &buffer[..read]);
+    }
+    Ok(format!("{:016x}", hash))
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub content_hash: String,
+    /// Backends and OCR state the text was extracted with, see `TextCache::extractor`
+    #[serde(default)]
+    pub extractor: String,
+}
+
+impl CacheEntry {
+    fn for_file(file_path: &Path) -> Result<CacheEntry, BoxError> {
+        let meta = fs::metadata(file_path)?;
+        let mtime = meta.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(CacheEntry {
+            path: file_path.to_path_buf(),
+            size: meta.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            content_hash: String::new(),
+            extractor: String::new(),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub missing_files: usize,
+    pub text_bytes: u64,
+}
+
+/// Writes a temporary file next to `path` and renames it into place, so no reader sees half a file.
+fn write_replace(path: &Path, data: &[u8]) -> Result<(), BoxError> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(".{}.tmp", std::process::id()));
+    fs::write(&temp_path, data)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Extracted text per file under the cache dir, stored as `<key>.toml` (file state) and `<key>.txt`.
+#[derive(Debug)]
+pub struct TextCache {
+    pub folder: PathBuf,
+    /// Backends and OCR state of this run, text extracted another way is a cache miss
+    pub extractor: String,
+}
+
+impl TextCache {
+    pub fn open() -> Result<TextCache, BoxError> {
+        let proj_dirs = ProjectDirs::from("", "", CACHE_FOLDER_NAME).ok_or("Could not find the cache folder")?;
+        let folder = proj_dirs.cache_dir().join(TEXT_FOLDER_NAME);
+        fs::create_dir_all(&folder)?;
+        Ok(TextCache { folder, extractor: String::new() })
+    }
+
+    fn entry_paths(&self, file_path: &Path) -> (PathBuf, PathBuf) {
+        let key = format!("{:016x}", utils::fnv1a(FNV_OFFSET, file_path.to_string_lossy().as_bytes()));
+        (self.folder.join(format!("{}.toml", key)), self.folder.join(format!("{}.txt", key)))
+    }
+
+    fn read_entry(meta_path: &Path) -> Result<CacheEntry, BoxError> {
+        Ok(toml::from_str(&fs::read_to_string(meta_path)?)?)
+    }
+
+    /// Cached text if the file is unchanged. A changed mtime with the same size and content still counts as unchanged.
+    pub fn get(&self, file_path: &Path) -> Option<Vec<u8>> {
+        let file_path = file_path.canonicalize().ok()?;
+        let (meta_path, text_path) = self.entry_paths(&file_path);
+        let mut cached = TextCache::read_entry(&meta_path).ok()?;
+        let current = CacheEntry::for_file(&file_path).ok()?;
+
+        if cached.path != current.path || cached.size != current.size || cached.extractor != self.extractor {
+            return None;
+        }
+        if (cached.mtime_secs, cached.mtime_nanos) != (current.mtime_secs, current.mtime_nanos) {
+            if content_hash(&file_path).ok()? != cached.content_hash {
+                return None;
+            }
+            cached.mtime_secs = current.mtime_secs;
+            cached.mtime_nanos = current.mtime_nanos;
+            let _ = toml::to_string(&cached).map(|s| write_replace(&meta_path, s.as_bytes()));
+        }
+        fs::read(text_path).ok()
+    }
+
+    pub fn put(&self, file_path: &Path, text: &[u8]) -> Result<(), BoxError> {
+        let file_path = file_path.canonicalize()?;
+        let (meta_path, text_path) = self.entry_paths(&file_path);
+        let mut entry = CacheEntry::for_file(&file_path)?;
+        entry.content_hash = content_hash(&file_path)?;
+        entry.extractor = self.extractor.clone();
+
+        // the entry only counts once the .toml exists, so write the text first
+        write_replace(&text_path, text)?;
+        write_replace(&meta_path, toml::to_string(&entry)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, CacheEntry)>, BoxError> {
+        let mut list = vec![];
+        for dir_entry in fs::read_dir(&self.folder)? {
+            let meta_path = dir_entry?.path();
+            if meta_path.extension().is_some_and(|ext| ext == "toml") {
+                if let Ok(entry) = TextCache::read_entry(&meta_path) {
+                    list.push((meta_path, entry));
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn stats(&self) -> Result<CacheStats, BoxError> {
+        let mut stats = CacheStats::default();
+        for (meta_path, entry) in self.entries()? {
+            stats.entries += 1;
+            if !entry.path.exists() {
+                stats.missing_files += 1;
+            }
+            stats.text_bytes += fs::metadata(meta_path.with_extension("txt")).map(|m| m.len()).unwrap_or(0);
+        }
+        Ok(stats)
+    }
+
+    /// Removes the entries of deleted files, returns the number of removed entries.
+    pub fn prune(&self) -> Result<usize, BoxError> {
+        let mut removed = 0;
+        for (meta_path, entry) in self.entries()? {
+            if !entry.path.exists() {
+                fs::remove_file(&meta_path)?;
+                let _ = fs::remove_file(meta_path.with_extension("txt"));
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> Result<(), BoxError> {
+        fs::remove_dir_all(&self.folder)?;
+        fs::create_dir_all(&self.folder)?;
+        Ok(())
+    }
+}
+
+/// Serves `pdf_text` from the `TextCache` and fills it on a miss.
+#[derive(Debug)]
+pub struct CachedTools {
+    pub tools: Box<dyn PDFTools + std::marker::Send + std::marker::Sync>,
+    pub cache: TextCache,
+}
+
+impl PDFTools for CachedTools {
+    fn pdf_info(&self, file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError> {
+        self.tools.pdf_info(file_path)
+    }
+
+    fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError> {
+        if let Some(text) = self.cache.get(file_path) {
+            return Ok(text);
+        }
+        let text = self.tools.pdf_text(file_path)?;
+        if let Err(e) = self.cache.put(file_path, &text) {
+            eprintln!("Could not cache text for {}: {}", file_path.display(), e);
+        }
+        Ok(text)
+    }
+
+    /// A cached text has all pages, a page range is not cached.
+    fn pdf_text_range(&self, file_path: &Path, first: usize, last: Option<usize>) -> Result<Vec<u8>, BoxError> {
+        match self.cache.get(file_path) {
+            Some(text) => Ok(text),
+            None => self.tools.pdf_text_range(file_path, first, last),
+        }
+    }
+
+    fn pdf_text_pages(&self, file_path: &Path, pages: Option<&PageRanges>) -> Result<Vec<u8>, BoxError> {
+        let Some(pages) = pages else {
+            return self.pdf_text(file_path);
+        };
+        match self.cache.get(file_path) {
+            Some(text) => Ok(text),
+            None => self.tools.pdf_text_pages(file_path, Some(pages)),
+        }
+    }
+
+    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
+        self.tools.split_pages(text)
+    }
+}
+
+#[test]
+fn test_cache_roundtrip() {
+    let folder = std::env::temp_dir().join(format!("pdf_grep_cache_test_{}", std::process::id()));
+    fs::create_dir_all(&folder).unwrap();
+    let cache = TextCache { folder: folder.clone(), extractor: "UsePoppler".into() };
+    let file = folder.join("sample.pdf");
+    fs::write(&file, b"%PDF-1.4 not really a pdf").unwrap();
+
+    assert!(cache.get(&file).is_none());
+    cache.put(&file, b"page one\x0cpage two").unwrap();
+    assert_eq!(cache.get(&file).unwrap(), b"page one\x0cpage two");
+    // text extracted without OCR is not served once OCR is available
+    let ocr_cache = TextCache { folder: folder.clone(), extractor: "UsePoppler ocr:eng".into() };
+    assert!(ocr_cache.get(&file).is_none());
+
+    // a changed file is a cache miss
+    fs::write(&file, b"%PDF-1.4 changed content").unwrap();
+    assert!(cache.get(&file).is_none());
+
+    fs::remove_file(&file).unwrap();
+    assert_eq!(cache.stats().unwrap().missing_files, 1);
+    assert_eq!(cache.prune().unwrap(), 1);
+    assert_eq!(cache.stats().unwrap().entries, 0);
+
+    fs::remove_dir_all(&folder).unwrap();
+}
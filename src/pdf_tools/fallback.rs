@@ -0,0 +1,155 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use xpdf_tools::{types::XpdfArgs, PdfError, XpdfTools};
+
+use crate::{settings::Settings, utils::{self, PageRanges}, BoxError};
+
+use super::{cache::{CachedTools, TextCache}, limit::{LimitedTools, Skipped, Slots}, ocr::{OcrTools, OcrWrapper}, AvailablePdfTools, PDFTools, PdfDummyTool, PdfExtractWrapper, PopplerWrapper, XpdfWrapper};
+
+/// Chain of working backends, a file is retried on the next backend when one fails.
+#[derive(Debug)]
+pub struct FallbackTools {
+    pub tools: Vec<Box<dyn PDFTools + std::marker::Send + std::marker::Sync>>,
+}
+
+impl FallbackTools {
+    fn first_ok<T, F>(&self, f: F) -> Result<T, BoxError>
+        where F: Fn(&dyn PDFTools) -> Result<T, BoxError>
+    {
+        let mut errors = vec![];
+        for tool in &self.tools {
+            match f(tool.as_ref()) {
+                Ok(result) => return Ok(result),
+                // each backend would run into the same limit again
+                Err(e) if e.is::<Skipped>() => return Err(e),
+                Err(e) => {
+                    if let Some(pdf_error) = e.downcast_ref::<PdfError>() {
+                        errors.push(format!("{}\n{}", pdf_error.message, pdf_error.process_message));
+                    } else {
+                        errors.push(e.to_string());
+                    }
+                }
+            }
+        }
+        Err(errors.join("\n").into())
+    }
+}
+
+impl PDFTools for FallbackTools {
+    fn pdf_info(&self, file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError> {
+        self.first_ok(|tool| tool.pdf_info(file_path))
+    }
+
+    fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError> {
+        self.first_ok(|tool| tool.pdf_text(file_path))
+    }
+
+    fn pdf_text_range(&self, file_path: &Path, first: usize, last: Option<usize>) -> Result<Vec<u8>, BoxError> {
+        self.first_ok(|tool| tool.pdf_text_range(file_path, first, last))
+    }
+
+    fn pdf_text_pages(&self, file_path: &Path, pages: Option<&PageRanges>) -> Result<Vec<u8>, BoxError> {
+        self.first_ok(|tool| tool.pdf_text_pages(file_path, pages))
+    }
+
+    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
+        // all backends separate pages with a form feed
+        Ok(text.split('\u{c}').collect::<Vec<_>>())
+    }
+}
+
+/// Creates the backend if it is usable on this machine.
+pub fn create_tool(kind: &AvailablePdfTools, settings: &Settings) -> Result<Box<dyn PDFTools + std::marker::Send + std::marker::Sync>, BoxError> {
+    match kind {
+        AvailablePdfTools::UseXpdfTools => {
+            let folder = settings.xpdf_tools_folder.as_ref().ok_or("xpdf_tools_folder is not set")?;
+            let tools = XpdfTools::builder(PathBuf::from(folder))
+                .map_err(|e| format!("xpdf tools not found in {}: {:?}", folder.display(), e))?
+                .extra_args(vec![XpdfArgs::Encoding("UTF-8".into())])
+                .build();
+            Ok(Box::new(XpdfWrapper {tools, tools_folder: folder.clone(), timeout: settings.timeout}))
+        },
+        AvailablePdfTools::UsePoppler => {
+            let tools = PopplerWrapper {tools_folder: settings.poppler_tools_folder.clone(), timeout: settings.timeout};
+            if tools.is_available() {
+                Ok(Box::new(tools))
+            } else {
+                Err("poppler pdftotext not found".into())
+            }
+        },
+        AvailablePdfTools::UsePdfExtract => Ok(Box::new(PdfExtractWrapper {})),
+        AvailablePdfTools::UsePdfDummyTool => Ok(Box::new(PdfDummyTool {})),
+    }
+}
+
+/// Picks the working backends in the order of `use_pdf_tool` followed by `pdf_tool_priority`.
+pub fn resolve_tools(settings: &Settings) -> Result<Box<dyn PDFTools + std::marker::Send + std::marker::Sync>, BoxError> {
+    let mut order = vec![settings.use_pdf_tool.clone()];
+    for kind in &settings.pdf_tool_priority {
+        if !order.contains(kind) {
+            order.push(kind.clone());
+        }
+    }
+
+    let mut tools = vec![];
+    let mut errors = vec![];
+    let mut used = vec![];
+    for kind in &order {
+        match create_tool(kind, settings) {
+            Ok(tool) => {
+                tools.push(tool);
+                used.push(format!("{:?}", kind));
+            },
+            Err(e) => errors.push(format!("{:?}: {}", kind, e)),
+        }
+    }
+
+    let tools: Box<dyn PDFTools + std::marker::Send + std::marker::Sync> = match tools.len() {
+        0 => return Err(format!("No usable pdf tool found:\n{}", errors.join("\n")).into()),
+        1 => tools.remove(0),
+        _ => Box::new(FallbackTools {tools}),
+    };
+
+    let ocr = OcrTools {
+        pdftoppm: utils::find_tool(&[settings.xpdf_tools_folder.as_deref(), settings.poppler_tools_folder.as_deref()], "pdftoppm"),
+        tesseract: settings.tesseract_path.clone().unwrap_or_else(|| PathBuf::from("tesseract")),
+        language: settings.ocr_language.clone(),
+        timeout: settings.timeout,
+    };
+    let force = settings.cli.as_ref().is_some_and(|cli| cli.ocr);
+    // the cached text depends on the backends and on OCR, tesseract may be installed later
+    let mut extractor = used.join(",");
+    let tools: Box<dyn PDFTools + std::marker::Send + std::marker::Sync> = if ocr.is_available() {
+        extractor.push_str(&format!(" ocr:{}", ocr.language));
+        Box::new(OcrWrapper {tools, ocr, force})
+    } else if force {
+        return Err(format!("--ocr needs tesseract, not found at: {}", ocr.tesseract.display()).into());
+    } else {
+        tools
+    };
+
+    // inside the cache, a cached text does not wait for a slot
+    let tools: Box<dyn PDFTools + std::marker::Send + std::marker::Sync> = if settings.max_parallel_extractions > 0 {
+        Box::new(LimitedTools {tools, slots: Slots::new(settings.max_parallel_extractions)})
+    } else {
+        tools
+    };
+
+    // forced OCR should not be answered from text cached without it
+    let no_cache = force || settings.cli.as_ref().is_some_and(|cli| cli.no_cache);
+    if settings.use_text_cache && !no_cache {
+        Ok(Box::new(CachedTools {tools, cache: TextCache { extractor, ..TextCache::open()? }}))
+    } else {
+        Ok(tools)
+    }
+}
+
+#[test]
+fn test_fallback_on_error() {
+    let tools = FallbackTools {
+        tools: vec![Box::new(PdfExtractWrapper {}), Box::new(PdfDummyTool {})],
+    };
+    // pdf-extract fails on a missing file, the dummy tool answers instead
+    let text = tools.pdf_text(Path::new("./data/does_not_exist.pdf")).unwrap();
+    assert!(String::from_utf8_lossy(&text).contains("test wrapper"));
+}
@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use pdf_extract::{Dictionary, Document, Object};
+
+use crate::BoxError;
+
+/// Numbering style of a page label range, the `/S` entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LabelStyle {
+    Decimal,
+    UpperRoman,
+    LowerRoman,
+    UpperLetters,
+    LowerLetters,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LabelRange {
+    /// 0-based index of the first page of the range
+    start: usize,
+    style: LabelStyle,
+    prefix: String,
+    first_number: usize,
+}
+
+fn roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [(1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i")];
+    let mut result = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            result.push_str(numeral);
+            n -= value;
+        }
+    }
+    result
+}
+
+/// 1 is "a", 26 is "z", 27 is "aa", 53 is "aaa".
+fn letters(n: usize) -> String {
+    let letter = (b'a' + ((n.max(1) - 1) % 26) as u8) as char;
+    letter.to_string().repeat((n.max(1) - 1) / 26 + 1)
+}
+
+impl LabelRange {
+    fn label(&self, page: usize) -> String {
+        let n = self.first_number + page - self.start;
+        let number = match self.style {
+            LabelStyle::Decimal => n.to_string(),
+            LabelStyle::UpperRoman => roman(n).to_uppercase(),
+            LabelStyle::LowerRoman => roman(n),
+            LabelStyle::UpperLetters => letters(n).to_uppercase(),
+            LabelStyle::LowerLetters => letters(n),
+            LabelStyle::None => String::new(),
+        };
+        format!("{}{}", self.prefix, number)
+    }
+
+    fn from_dict(start: usize, dict: &Dictionary) -> LabelRange {
+        let style = match dict.get(b"S").and_then(Object::as_name) {
+            Ok(b"D") => LabelStyle::Decimal,
+            Ok(b"R") => LabelStyle::UpperRoman,
+            Ok(b"r") => LabelStyle::LowerRoman,
+            Ok(b"A") => LabelStyle::UpperLetters,
+            Ok(b"a") => LabelStyle::LowerLetters,
+            _ => LabelStyle::None,
+        };
+        let prefix = dict.get(b"P").ok().and_then(|p| pdf_extract::decode_text_string(p).ok()).unwrap_or_default();
+        let first_number = dict.get(b"St").and_then(Object::as_i64).map_or(1, |st| st.max(1) as usize);
+        LabelRange { start, style, prefix, first_number }
+    }
+}
+
+/// Collects the `/Nums` entries of the number tree node and its `/Kids`.
+fn collect_ranges(doc: &Document, node: &Object, ranges: &mut Vec<LabelRange>, depth: usize) -> Result<(), BoxError> {
+    if depth > 32 {
+        return Err("page label tree is nested too deep".into());
+    }
+    let node = doc.dereference(node)?.1.as_dict()?;
+    if let Ok(nums) = node.get(b"Nums").and_then(Object::as_array) {
+        for pair in nums.chunks(2) {
+            if let [start, dict] = pair {
+                let start = doc.dereference(start)?.1.as_i64()?.max(0) as usize;
+                ranges.push(LabelRange::from_dict(start, doc.dereference(dict)?.1.as_dict()?));
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+        for kid in kids {
+            collect_ranges(doc, kid, ranges, depth + 1)?;
+        }
+    }
+    Ok(())
+}
+
+/// Printed page labels ("ix", "A-3", "7") of every page from the `/PageLabels` tree of the catalog,
+/// `None` if the file has none and the labels are just the page numbers.
+pub fn page_labels(file_path: &Path) -> Result<Option<Vec<String>>, BoxError> {
+    let doc = Document::load_mem(&std::fs::read(file_path)?)?;
+    let Ok(tree) = doc.catalog()?.get(b"PageLabels") else {
+        return Ok(None);
+    };
+
+    let mut ranges = vec![];
+    collect_ranges(&doc, tree, &mut ranges, 0)?;
+    ranges.sort_by_key(|range| range.start);
+    Ok(Some(labels_for_pages(&ranges, doc.get_pages().len())))
+}
+
+fn labels_for_pages(ranges: &[LabelRange], pages: usize) -> Vec<String> {
+    (0..pages).map(|page| {
+        match ranges.iter().rev().find(|range| range.start <= page) {
+            Some(range) => range.label(page),
+            None => (page + 1).to_string(),
+        }
+    }).collect()
+}
+
+#[test]
+fn test_labels_for_pages() {
+    let ranges = vec![
+        LabelRange { start: 0, style: LabelStyle::LowerRoman, prefix: String::new(), first_number: 1 },
+        LabelRange { start: 4, style: LabelStyle::Decimal, prefix: String::new(), first_number: 1 },
+        LabelRange { start: 6, style: LabelStyle::Decimal, prefix: "A-".into(), first_number: 8 },
+        LabelRange { start: 7, style: LabelStyle::UpperLetters, prefix: String::new(), first_number: 27 },
+    ];
+    assert_eq!(labels_for_pages(&ranges, 8), ["i", "ii", "iii", "iv", "1", "2", "A-8", "AA"]);
+}
@@ -0,0 +1,185 @@
+use std::{cell::Cell, collections::BTreeMap, fmt, io::Read, path::Path, process::{Command, Output, Stdio}, sync::{Condvar, Mutex}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use crate::{settings::Settings, utils::PageRanges, BoxError};
+
+use super::PDFTools;
+
+/// A file left out on purpose: over `--max-filesize` or `--max-pages`, or a backend ran into `--timeout`.
+/// A directory search lists these files at the end instead of failing on them.
+#[derive(Debug)]
+pub struct Skipped(pub String);
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+thread_local! {
+    /// What is left of `--timeout` for the file searched on this thread, see `with_file_timeout`
+    static FILE_TIMEOUT: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with one `--timeout` for all the backend processes it starts, their run times add up.
+/// Waiting for a free `max_parallel_extractions` slot doesn't count.
+pub fn with_file_timeout<T>(timeout: Option<Duration>, f: impl FnOnce() -> T) -> T {
+    let outer = FILE_TIMEOUT.replace(timeout);
+    let result = f();
+    FILE_TIMEOUT.set(outer);
+    result
+}
+
+/// Runs a backend process, with a timeout the process is killed when it runs longer.
+/// Within `with_file_timeout` the time left for the file is the timeout.
+pub fn run_command(name: &str, command: &mut Command, timeout: Option<Duration>) -> Result<Output, BoxError> {
+    let file_timeout = FILE_TIMEOUT.get();
+    let Some(timeout) = file_timeout.or(timeout) else {
+        return Ok(command.output().map_err(|e| format!("Could not run {}: {}", name, e))?);
+    };
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not run {}: {}", name, e))?;
+
+    // the pipes are read while waiting, a full pipe would block the process until the timeout
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            if file_timeout.is_some() {
+                FILE_TIMEOUT.set(Some(Duration::ZERO));
+                return Err(Box::new(Skipped(format!("{} ran into the --timeout of the file", name))));
+            }
+            return Err(Box::new(Skipped(format!("{} timed out after {}s", name, timeout.as_secs()))));
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    if file_timeout.is_some() {
+        FILE_TIMEOUT.set(Some(timeout.saturating_sub(start.elapsed())));
+    }
+    Ok(Output { status, stdout: stdout.join().unwrap_or_default(), stderr: stderr.join().unwrap_or_default() })
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// Checks `--max-filesize` and `--max-pages` before the text is extracted.
+/// The page count comes from the backend info, a file without one is searched.
+pub fn check_file(file: &Path, settings: &Settings) -> Result<(), BoxError> {
+    if let Some(max) = settings.max_filesize {
+        let size = std::fs::metadata(file)?.len();
+        if size > max {
+            return Err(Box::new(Skipped(format!("{} bytes, over --max-filesize {}", size, max))));
+        }
+    }
+    if let Some(max) = settings.max_pages {
+        let pages = settings.tools.pdf_info(file)?
+            .get("Pages").cloned().flatten()
+            .and_then(|pages| pages.trim().parse::<usize>().ok());
+        if let Some(pages) = pages.filter(|pages| *pages > max) {
+            return Err(Box::new(Skipped(format!("{} pages, over --max-pages {}", pages, max))));
+        }
+    }
+    Ok(())
+}
+
+/// Counting semaphore, a slot is held while a backend call runs.
+#[derive(Debug)]
+pub struct Slots {
+    free: Mutex<usize>,
+    released: Condvar,
+}
+
+struct SlotGuard<'a>(&'a Slots);
+
+impl Drop for SlotGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        self.0.released.notify_one();
+    }
+}
+
+impl Slots {
+    pub fn new(count: usize) -> Slots {
+        Slots { free: Mutex::new(count.max(1)), released: Condvar::new() }
+    }
+
+    fn acquire(&self) -> SlotGuard<'_> {
+        let mut free = self.free.lock().unwrap_or_else(|e| e.into_inner());
+        while *free == 0 {
+            free = self.released.wait(free).unwrap_or_else(|e| e.into_inner());
+        }
+        *free -= 1;
+        SlotGuard(self)
+    }
+}
+
+/// Runs at most `max_parallel_extractions` calls of the wrapped backend at once, most of them start
+/// a pdftotext or tesseract process. Searching the extracted text is not limited.
+#[derive(Debug)]
+pub struct LimitedTools {
+    pub tools: Box<dyn PDFTools + std::marker::Send + std::marker::Sync>,
+    pub slots: Slots,
+}
+
+impl PDFTools for LimitedTools {
+    fn pdf_info(&self, file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError> {
+        let _slot = self.slots.acquire();
+        self.tools.pdf_info(file_path)
+    }
+
+    fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError> {
+        let _slot = self.slots.acquire();
+        self.tools.pdf_text(file_path)
+    }
+
+    fn pdf_text_range(&self, file_path: &Path, first: usize, last: Option<usize>) -> Result<Vec<u8>, BoxError> {
+        let _slot = self.slots.acquire();
+        self.tools.pdf_text_range(file_path, first, last)
+    }
+
+    fn pdf_text_pages(&self, file_path: &Path, pages: Option<&PageRanges>) -> Result<Vec<u8>, BoxError> {
+        let _slot = self.slots.acquire();
+        self.tools.pdf_text_pages(file_path, pages)
+    }
+
+    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
+        self.tools.split_pages(text)
+    }
+}
+
+#[test]
+fn test_slots_limit() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let slots = Slots::new(2);
+    let running = AtomicUsize::new(0);
+    let most = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..6 {
+            scope.spawn(|| {
+                let _slot = slots.acquire();
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+    assert_eq!(most.load(Ordering::SeqCst), 2);
+}
@@ -1,20 +1,34 @@
-use std::{collections::BTreeMap, io:: Write, path::Path};
+use std::{cmp::Ordering, collections::BTreeMap, io:: Write, path::{Path, PathBuf}, sync::Mutex, time::Duration};
 use core::fmt::Debug;
 
-use grep::regex::RegexMatcher;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use xpdf_tools::{PdfError, XpdfTools};
 
-use crate::{grep_utils::{self}, settings::{FolderSearchMode, Settings}, utils, BoxError};
+use crate::{grep_utils::{self, PdfPage}, normalize::{self, FoldedPage}, patterns::PatternMatcher, settings::{FolderSearchMode, InfoColumn, OutputMode, Settings, SortMode}, utils::{self, PageRanges}, BoxError};
 
 use rayon::prelude::*;
 
-use termcolor::{BufferedStandardStream, Color, ColorSpec, WriteColor};
+use termcolor::{Buffer, BufferedStandardStream, Color, ColorSpec, WriteColor};
+
+pub use self::cache::TextCache;
+pub use self::fallback::resolve_tools;
+pub use self::labels::page_labels;
+pub use self::limit::Skipped;
+pub use self::poppler::PopplerWrapper;
+
+mod cache;
+mod fallback;
+mod labels;
+mod limit;
+mod ocr;
+mod poppler;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum AvailablePdfTools {
     UseXpdfTools,
+    UsePdfExtract,
+    UsePoppler,
     UsePdfDummyTool,
 }
 
@@ -23,7 +37,224 @@ pub trait PDFTools: std::fmt::Debug {
     fn pdf_info(&self, file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError>;
     fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError>;
     fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError>;
-    fn search_file(&self, file: &Path, pattern: &str, settings: &Settings) -> Result<(), BoxError>;
+
+    /// Text of the pages `first..=last` (1-based), `last` None runs to the end. Backends that can extract
+    /// a page range pad the skipped pages before `first` with form feeds, so page numbers stay absolute.
+    fn pdf_text_range(&self, file_path: &Path, _first: usize, _last: Option<usize>) -> Result<Vec<u8>, BoxError> {
+        self.pdf_text(file_path)
+    }
+
+    /// Text of the pages selected with `--pages`, or of the whole file.
+    fn pdf_text_pages(&self, file_path: &Path, pages: Option<&PageRanges>) -> Result<Vec<u8>, BoxError> {
+        match pages {
+            Some(pages) => self.pdf_text_range(file_path, pages.first(), pages.last()),
+            None => self.pdf_text(file_path),
+        }
+    }
+
+    /// Searches the file and writes the results to `wtr`, the caller prints the whole buffer at once.
+    fn search_file(&self, file: &Path, settings: &Settings, wtr: &mut Buffer) -> Result<u32, BoxError> {
+        
+        //let file_path = file;
+        let mut total = 0;
+        
+        match self.pdf_text_pages(file, settings.page_ranges.as_ref()) {
+            Ok(content) => {
+                let text = String::from_utf8_lossy(&content);
+                let pages = self.split_pages(&text)?;
+                let (matcher, query_pages) = match (&settings.query, &settings.patterns) {
+                    (Some(query), _) => (PatternMatcher::Regex(query.highlight.clone()), Some(query.matching_pages(&pages, settings))),
+                    (None, Some(patterns)) => (patterns.matcher.clone(), None),
+                    (None, None) => return Err("No pattern to search for".into()),
+                };
+                let selected = |page: usize| settings.page_selected(page) && query_pages.as_ref().map_or(true, |pages| pages.contains(&page));
+                if let Some(kind) = settings.summary_kind {
+                    let pages: Vec<_> = pages.iter().enumerate()
+                        .filter(|(page, _)| selected(*page))
+                        .map(|(page, text)| (page, normalize::normalize_page(text, settings)))
+                        .collect();
+                    return Ok(grep_utils::search_pdf_summary(&matcher, wtr, file, &pages, kind, settings)?);
+                }
+                // a query lists only the files it matches
+                if query_pages.as_ref().is_some_and(|pages| pages.is_empty()) {
+                    return Ok(0);
+                }
+                let labels = if settings.page_labels {
+                    page_labels(file).unwrap_or_else(|e| {
+                        eprintln!("No page labels for {}: {}", file.display(), e);
+                        None
+                    })
+                } else {
+                    None
+                };
+
+                match settings.output_mode {
+                    OutputMode::Standard => {
+                        let mut printer = settings.create_page_printer(wtr, 0);
+                        let file_header = format!("Searching: {}\n", file.display());
+                        let p = printer.get_mut();
+                        p.set_color(&settings.info_color_spec).unwrap();
+                        p.write_all(file_header.as_bytes()).unwrap();
+                        if let Some(query_pages) = &query_pages {
+                            let list = query_pages.iter().map(|page| (page + 1).to_string()).collect::<Vec<_>>().join(", ");
+                            p.write_all(format!("Matching pages: {}\n", list).as_bytes()).unwrap();
+                        }
+
+                        for (page, split) in pages.iter().enumerate() {
+                            if !selected(page) {
+                                continue;
+                            }
+                            printer = settings.create_page_printer(printer.into_inner(), page);
+                            let (split, boundary) = normalize::page_with_overlap(split, pages.get(page + 1).copied(), settings.page_overlap, settings);
+                            let pdf_page = PdfPage { text: split.as_bytes(), page, boundary, labels: labels.as_deref() };
+                            let folded = FoldedPage::new(&split, &settings.fold_options);
+                            let search_result = grep_utils::search_pdf_page(&folded.matcher(&matcher), &mut printer, &pdf_page, settings);
+                            match search_result {
+                                Ok(count) => {
+                                    total += count;
+                                },
+                                Err(e) => eprint!("{}", e),
+                            }
+                        }
+
+                        let file_footer = format!("End of file: found {} matches.\n\n", total);
+                        let p = printer.get_mut();
+                        p.set_color(&settings.info_color_spec).unwrap();
+                        p.write_all(file_footer.as_bytes()).unwrap();
+
+                        p.reset().unwrap();
+                    },
+                    OutputMode::Json => {
+                        let path = file.display().to_string();
+                        let mut begin = serde_json::json!({"type": "begin", "data": {"path": path}});
+                        if let Some(query_pages) = &query_pages {
+                            begin["data"]["matching_pages"] = query_pages.iter().map(|page| page + 1).collect();
+                        }
+                        grep_utils::write_json_line(wtr, &begin)?;
+                        let mut matched_lines = 0;
+                        for (page, split) in pages.iter().enumerate() {
+                            if !selected(page) {
+                                continue;
+                            }
+                            let (split, boundary) = normalize::page_with_overlap(split, pages.get(page + 1).copied(), settings.page_overlap, settings);
+                            let pdf_page = PdfPage { text: split.as_bytes(), page, boundary, labels: labels.as_deref() };
+                            let folded = FoldedPage::new(&split, &settings.fold_options);
+                            match grep_utils::search_pdf_page_json(&folded.matcher(&matcher), wtr, &pdf_page, &path, settings) {
+                                Ok((matches, lines)) => {
+                                    total += matches as u32;
+                                    matched_lines += lines;
+                                },
+                                Err(e) => eprint!("{}", e),
+                            }
+                        }
+                        grep_utils::write_json_line(wtr, &serde_json::json!({"type": "end", "data": {
+                            "path": path,
+                            "stats": {"matches": total, "matched_lines": matched_lines, "bytes_searched": content.len()},
+                        }}))?;
+                    },
+                }
+            },
+            Err(e) => return Err(e),
+        };
+
+        
+
+        //stdout.reset()?;
+        Ok(total)
+    }
+}
+
+/// Prints the error of a file to stderr, for xpdf errors also the output of the tool.
+pub fn report_error(file: &Path, e: &BoxError) {
+    let mut stderr = BufferedStandardStream::stderr(termcolor::ColorChoice::Auto);
+    stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red))).unwrap();
+    write!(stderr, "{}: ", file.display()).unwrap();
+    if let Some(pdf_error) = e.downcast_ref::<PdfError>() {
+        stderr.write_all(pdf_error.message.as_bytes()).unwrap();
+        stderr.write_all(b"\n").unwrap();
+        stderr.write_all(pdf_error.process_message.as_bytes()).unwrap();
+        
+    } else {
+        stderr.write_all(e.to_string().as_bytes()).unwrap();
+    }
+    stderr.write_all(b"\n").unwrap();
+    stderr.flush().unwrap();
+    stderr.reset().unwrap();
+}
+
+/// Lists the files a directory search left out, after all results.
+pub fn report_skipped(skipped: &[(String, String)]) {
+    if skipped.is_empty() {
+        return;
+    }
+    let mut stderr = BufferedStandardStream::stderr(termcolor::ColorChoice::Auto);
+    stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow))).unwrap();
+    writeln!(stderr, "Skipped {} file(s):", skipped.len()).unwrap();
+    stderr.reset().unwrap();
+    for (file, reason) in skipped {
+        writeln!(stderr, "  {}: {}", file, reason).unwrap();
+    }
+    stderr.flush().unwrap();
+}
+
+/// Outcome of the search of one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileResult {
+    Matches(u32),
+    /// Left out with `--max-filesize`, `--max-pages` or `--timeout`, only listed in the report
+    Skipped,
+    /// The search failed, the error is reported
+    Failed,
+}
+
+impl FileResult {
+    pub fn matches(self) -> Option<u32> {
+        match self {
+            FileResult::Matches(count) => Some(count),
+            _ => None,
+        }
+    }
+}
+
+/// Match count of the file after the `--max-filesize` and `--max-pages` checks, with one `--timeout` for the file.
+fn search_checked(file: &Path, settings: &Settings, wtr: &mut Buffer) -> Result<u32, BoxError> {
+    limit::with_file_timeout(settings.timeout, || {
+        limit::check_file(file, settings)?;
+        settings.tools.search_file(file, settings, wtr)
+    })
+}
+
+/// Text of the whole file within the same limits as a search of it.
+pub fn extract_checked(file: &Path, settings: &Settings) -> Result<Vec<u8>, BoxError> {
+    limit::with_file_timeout(settings.timeout, || {
+        limit::check_file(file, settings)?;
+        settings.tools.pdf_text(file)
+    })
+}
+
+/// Searches the file, errors are reported right away and the skipped files in `skipped`.
+fn search_reported(file: &Path, settings: &Settings, wtr: &mut Buffer, skipped: &Mutex<Vec<(String, String)>>) -> FileResult {
+    match search_checked(file, settings, wtr) {
+        Ok(count) => FileResult::Matches(count),
+        Err(e) => match e.downcast_ref::<Skipped>() {
+            Some(skip) => {
+                skipped.lock().unwrap().push((file.display().to_string(), skip.to_string()));
+                FileResult::Skipped
+            },
+            None => {
+                report_error(file, &e);
+                FileResult::Failed
+            },
+        },
+    }
+}
+
+/// Result of the search of a single file, a skipped file is reported like in a directory search.
+pub fn search_one(file: &Path, settings: &Settings, wtr: &mut Buffer) -> FileResult {
+    let skipped = Mutex::new(vec![]);
+    let result = search_reported(file, settings, wtr, &skipped);
+    report_skipped(&skipped.into_inner().unwrap());
+    result
 }
 
 // impl Debug for dyn PDFTools + std::marker::Send + std::marker::Sync {
@@ -34,34 +265,84 @@ pub trait PDFTools: std::fmt::Debug {
 #[derive(Debug)]
 pub struct XpdfWrapper {
     pub tools: XpdfTools,
+    pub tools_folder: PathBuf,
+    /// With a `--timeout` the binaries are run like the poppler ones, the xpdf_tools calls can't be stopped
+    pub timeout: Option<Duration>,
+}
+
+impl XpdfWrapper {
+    fn poppler(&self) -> PopplerWrapper {
+        PopplerWrapper { tools_folder: Some(self.tools_folder.clone()), timeout: self.timeout }
+    }
 }
 #[derive(Debug)]
 pub struct  PdfDummyTool {
     
 }
 
-//much slower and has error for glyph extract
-// pub struct PdfExtractWrapper {
+/// Pure-Rust backend on top of `pdf-extract`, needs no external binaries.
+/// Slower than xpdf and may fail on some embedded fonts.
+#[derive(Debug)]
+pub struct PdfExtractWrapper {
 
-// }
+}
 
+impl PdfExtractWrapper {
+    fn load_document(file_path: &Path) -> Result<pdf_extract::Document, BoxError> {
+        let bytes = std::fs::read(file_path)?;
+        let doc = pdf_extract::Document::load_mem(&bytes)?;
+        Ok(doc)
+    }
+}
 
-// impl PDFTools for PdfExtractWrapper {
-//     fn pdf_info(&self, _file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError> {
-//         todo!()
-//     }
+impl PDFTools for PdfExtractWrapper {
+    fn pdf_info(&self, file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError> {
+        let doc = PdfExtractWrapper::load_document(file_path)?;
+
+        // same keys as the xpdf pdfinfo output
+        let mut info: BTreeMap<String, Option<String>> = ["Title", "Subject", "Keywords", "Author", "Creator", "Producer", "CreationDate", "ModDate"]
+            .iter()
+            .map(|key| (key.to_string(), None))
+            .collect();
+
+        if let Ok(info_obj) = doc.trailer.get(b"Info") {
+            if let Ok((_, pdf_extract::Object::Dictionary(dict))) = doc.dereference(info_obj) {
+                for (key, value) in dict.iter() {
+                    let key = String::from_utf8_lossy(key).to_string();
+                    let value = pdf_extract::decode_text_string(value).ok().filter(|v| !v.trim().is_empty());
+                    info.insert(key, value);
+                }
+            }
+        }
+        info.insert("Pages".into(), Some(doc.get_pages().len().to_string()));
+        info.insert("Encrypted".into(), Some(if doc.is_encrypted() { "yes" } else { "no" }.into()));
+        info.insert("PDF version".into(), Some(doc.version.clone()));
+        info.insert("File size".into(), Some(format!("{} bytes", std::fs::metadata(file_path)?.len())));
+
+        Ok(info)
+    }
+
+    fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError> {
+        let bytes = std::fs::read(file_path)?;
+        // pdf-extract panics on some broken fonts, don't take the whole search down with it
+        match std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(&bytes)) {
+            Ok(Ok(pages)) => Ok(pages.join("\u{c}").into_bytes()),
+            Ok(Err(e)) => Err(Box::new(e)),
+            Err(_) => Err(format!("pdf-extract failed on: {}", file_path.display()).into()),
+        }
+    }
+
+    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
+        Ok(text.split('\u{c}').collect::<Vec<_>>())
+    }
+}
 
-//     fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError> {
-//         let bytes = std::fs::read(file_path).unwrap();
-//         match pdf_extract::extract_text_from_mem(&bytes) {
-//             Ok(text) => Ok(text.as_bytes().to_vec()),
-//             Err(e) => Err(Box::new(e)),
-//         }
-//     }
-// }
 impl PDFTools for XpdfWrapper {
     
     fn pdf_info(&self, file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError> {
+        if self.timeout.is_some() {
+            return self.poppler().pdf_info(file_path);
+        }
         match self.tools.pdf_info(file_path) {
             Ok(pdf_info) => Ok(pdf_info.info_map.0),
             Err(e) => Err(Box::new(e)) 
@@ -69,71 +350,23 @@ impl PDFTools for XpdfWrapper {
     }
 
     fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError> {
+        if self.timeout.is_some() {
+            return self.poppler().pdf_text(file_path);
+        }
         match self.tools.pdf_text(file_path) {
             Ok(text) => Ok(text),
             Err(e) => Err(Box::new(e)),
         }
     }
 
-    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
-        Ok(text.split('\u{c}').collect::<Vec<_>>())
+    fn pdf_text_range(&self, file_path: &Path, first: usize, last: Option<usize>) -> Result<Vec<u8>, BoxError> {
+        // the xpdf pdftotext takes the same page arguments as the poppler one
+        self.poppler().pdf_text_range(file_path, first, last)
     }
-    fn search_file(&self, file: &Path, pattern: &str, settings: &Settings) -> Result<(), BoxError> {
-        
-        let mut printer = settings.create_printer();
-        //let file_path = file;
-        
-        match settings.tools.pdf_text(file) {
-            Ok(content) => {
-
-                let file_header = format!("Searching: {}\n", file.display());
-                let p = printer.get_mut();
-                p.set_color(&settings.info_color_spec).unwrap();
-                p.write_all(file_header.as_bytes()).unwrap();
-                
-                let s = format!(r"(?i){}", pattern);
-                let matcher = RegexMatcher::new(s.as_str())?;
-                
-                let mut total = 0;
-                for (page, split) in String::from_utf8_lossy(&content).split('\u{c}').enumerate() {
-                    let search_result = grep_utils::search_pdf_page(&matcher, &mut printer, split.as_bytes(), page, settings);
-                    match search_result {
-                        Ok(count) => {
-                            total += count;
-                        },
-                        Err(e) => eprint!("{}", e),
-                    }
-                }
 
-                let file_footer = format!("End of file: found {} matches.\n\n", total);
-                let p = printer.get_mut();
-                p.set_color(&settings.info_color_spec).unwrap();
-                p.write_all(file_footer.as_bytes()).unwrap();
-
-                p.reset().unwrap();
-            },
-            Err(e) => {
-                let mut stderr = BufferedStandardStream::stderr(termcolor::ColorChoice::Auto);
-                stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red))).unwrap();
-                if let Some(pdf_error) = e.downcast_ref::<PdfError>() {
-                    stderr.write_all(pdf_error.message.as_bytes()).unwrap();
-                    stderr.write_all(b"\n").unwrap();
-                    stderr.write_all(pdf_error.process_message.as_bytes()).unwrap();
-                    
-                } else {
-                    stderr.write_all(e.to_string().as_bytes()).unwrap();
-                }
-                stderr.flush().unwrap();
-                stderr.reset().unwrap();
-            },
-        };
-
-        
-
-        //stdout.reset()?;
-        Ok(())
+    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
+        Ok(text.split('\u{c}').collect::<Vec<_>>())
     }
-
 } 
 
 impl PDFTools for PdfDummyTool {
@@ -148,11 +381,8 @@ impl PDFTools for PdfDummyTool {
         Ok(text)
     }
 
-    fn split_pages<'a>(&self, _text: &'a str) -> Result<Vec<&'a str>, BoxError> {
-        unimplemented!();
-    }
-    fn search_file(&self, _file: &Path, _pattern: &str, _settings: &Settings) -> Result<(), BoxError> {
-        unimplemented!();
+    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
+        Ok(text.split('\u{c}').collect::<Vec<_>>())
     }
 }
 
@@ -166,56 +396,220 @@ pub fn get_info_file(file_path: &Path, settings: &Settings) {
     }
 }
 
-pub fn get_info_dir(dir_path: &Path) {
-    println!("info for: {:?}", dir_path);
+#[derive(Debug)]
+struct InfoRow {
+    file: String,
+    info: BTreeMap<String, Option<String>>,
+    size: u64,
 }
 
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+/// A pdf date as a sortable number like 20240305101201. Reads the pdfinfo form "Tue Mar  5 10:12:01 2024 CET",
+/// its ISO form "2024-03-05T10:12:01+01" and the raw "D:20240305101201+01'00'" of pdf-extract.
+/// The time zone is left out, it is not known for the pdfinfo form.
+fn parse_pdf_date(date: &str) -> Option<u64> {
+    let date = date.trim();
+    // a missing month or day counts as the first, a missing time as midnight
+    let padded = |digits: String| -> Option<u64> {
+        (4..=14).contains(&digits.len()).then(|| format!("{}{}", digits, &"00000101000000"[digits.len()..]).parse().ok())?
+    };
+    if let Some(raw) = date.strip_prefix("D:") {
+        return padded(raw.chars().take_while(char::is_ascii_digit).take(14).collect());
+    }
+    if date.get(4..5) == Some("-") {
+        // the offset after the time would add digits
+        return padded(date.chars().take(19).filter(char::is_ascii_digit).collect());
+    }
 
-pub fn search_dir(dir_path: &Path, pattern: &str, settings: &Settings) -> Result<(), BoxError> {
-   
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    let [_, month, day, time, year, ..] = parts[..] else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|m| month.to_lowercase().starts_with(m))? as u64 + 1;
+    let day: u64 = day.parse().ok()?;
+    let year: u64 = year.parse().ok()?;
+    let time: Vec<u64> = time.split(':').map(|n| n.parse().ok()).collect::<Option<_>>()?;
+    let [hour, minute, second] = time[..] else {
+        return None;
+    };
+    Some(((((year * 100 + month) * 100 + day) * 100 + hour) * 100 + minute) * 100 + second)
+}
+
+impl InfoRow {
+    fn text(&self, column: &InfoColumn) -> String {
+        let value = |key: &str| self.info.get(key).cloned().flatten().unwrap_or_default();
+        match column {
+            InfoColumn::File => self.file.clone(),
+            InfoColumn::Title => value("Title"),
+            InfoColumn::Author => value("Author"),
+            InfoColumn::Pages => value("Pages"),
+            InfoColumn::Producer => value("Producer"),
+            InfoColumn::Created => value("CreationDate"),
+            InfoColumn::Size => self.size.to_string(),
+        }
+    }
+
+    fn compare(&self, other: &InfoRow, column: &InfoColumn) -> Ordering {
+        match column {
+            InfoColumn::Size => self.size.cmp(&other.size),
+            InfoColumn::Pages => {
+                let pages = |row: &InfoRow| row.text(column).parse::<u64>().ok();
+                pages(self).cmp(&pages(other))
+            },
+            // unknown dates last
+            InfoColumn::Created => match (parse_pdf_date(&self.text(column)), parse_pdf_date(&other.text(column))) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (a, b) => a.is_none().cmp(&b.is_none()),
+            },
+            _ => self.text(column).to_lowercase().cmp(&other.text(column).to_lowercase()),
+        }
+    }
+}
+
+const INFO_MAX_WIDTH: usize = 50;
+
+pub fn get_info_dir(dir_path: &Path, columns: &[InfoColumn], sort: &[InfoColumn], settings: &Settings) -> Result<(), BoxError> {
+    let pdf_files = utils::get_folder_files(dir_path, &settings.walk)?;
+
+    let results: Vec<_> = pdf_files.par_iter().map(|(_, file)| {
+        let path = Path::new(file);
+        settings.tools.pdf_info(path).map(|info| InfoRow {
+            file: file.clone(),
+            info,
+            size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        }).map_err(|e| format!("{}: {}", file, e))
+    }).collect();
+
+    let (mut rows, mut errors) = (vec![], vec![]);
+    for result in results {
+        match result {
+            Ok(row) => rows.push(row),
+            Err(e) => errors.push(e),
+        }
+    }
+    rows.sort_by(|a, b| {
+        sort.iter().fold(Ordering::Equal, |order, column| order.then_with(|| a.compare(b, column)))
+            .then_with(|| a.file.cmp(&b.file))
+    });
+
+    let cells: Vec<Vec<String>> = rows.iter()
+        .map(|row| columns.iter().map(|c| row.text(c).chars().take(INFO_MAX_WIDTH).collect()).collect())
+        .collect();
+    let headers: Vec<String> = columns.iter().map(|c| format!("{:?}", c)).collect();
+    let widths: Vec<usize> = headers.iter().enumerate()
+        .map(|(i, h)| cells.iter().map(|row| row[i].chars().count()).chain([h.len()]).max().unwrap_or(0))
+        .collect();
+
+    let format_row = |row: &[String]| row.iter().zip(&widths)
+        .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    let mut stdout = BufferedStandardStream::stdout(settings.color_choice);
+    stdout.set_color(&settings.info_color_spec).unwrap();
+    writeln!(stdout, "{}", format_row(&headers).trim_end()).unwrap();
+    stdout.reset().unwrap();
+    for row in &cells {
+        writeln!(stdout, "{}", format_row(row).trim_end()).unwrap();
+    }
+    stdout.flush().unwrap();
+
+    for e in errors {
+        eprintln!("{}", e);
+    }
+    Ok(())
+}
+
+
+/// Results of all files in the folder.
+/// The files are searched in parallel, the output of each file is printed in one piece:
+/// right away with `--sort none`, else after all files in the `--sort` order.
+/// Errors are reported as they happen, the skipped files at the end.
+pub fn search_dir(dir_path: &Path, settings: &Settings) -> Result<Vec<FileResult>, BoxError> {
+    let stdout = settings.create_buffer_writer();
+    let skipped = Mutex::new(vec![]);
+    let search = |file: &String| {
+        let mut buffer = stdout.buffer();
+        let count = search_reported(Path::new(file), settings, &mut buffer, &skipped);
+        // a printed buffer is dropped right away, only the sorted output is held until the end
+        if settings.sort_mode == SortMode::None {
+            stdout.print(&buffer).unwrap_or_else(|e| eprintln!("{e}"));
+            return (file.clone(), count, None);
+        }
+        (file.clone(), count, Some(buffer))
+    };
+
+    let mut results = vec![];
     if settings.folder_search_mode == FolderSearchMode::ThreadPerFolder {
-        let pdf_map = utils::get_folder_tree(dir_path);
+        let pdf_map = utils::get_folder_tree(dir_path, &settings.walk)?;
 
-        pdf_map.par_iter().for_each(|(_, list)| {
-            for file in list {
-                settings.tools.search_file(Path::new(file), pattern, settings).unwrap_or_else(|e| eprintln!("{e}"))
-            }
-         });
+        results = pdf_map.par_iter().flat_map(|(_, list)| {
+            list.iter().map(search).collect::<Vec<_>>()
+         }).collect();
     } else if settings.folder_search_mode == FolderSearchMode::ThreadPerFile {
-        let pdf_files = utils::get_folder_files(dir_path);
+        let pdf_files = utils::get_folder_files(dir_path, &settings.walk)?;
         
-        pdf_files.par_iter().for_each(|(_dir, file)| {
-            settings.tools.search_file(Path::new(file), pattern, settings).unwrap_or_else(|e| eprintln!("{e}"))
-        });
+        results = pdf_files.par_iter().map(|(_dir, file)| search(file)).collect();
     }
-    Ok(())
+
+    let mut skipped = skipped.into_inner().unwrap();
+    skipped.sort();
+    match settings.sort_mode {
+        SortMode::None => {
+            report_skipped(&skipped);
+            return Ok(results.into_iter().map(|(_, count, _)| count).collect());
+        },
+        SortMode::Path => results.sort_by(|a, b| a.0.cmp(&b.0)),
+        SortMode::Mtime => results.sort_by_cached_key(|(file, _, _)| std::fs::metadata(file).and_then(|m| m.modified()).ok()),
+        // most matches first, then by path
+        SortMode::Matches => results.sort_by(|a, b| b.1.matches().cmp(&a.1.matches()).then_with(|| a.0.cmp(&b.0))),
+    }
+    for (_, _, buffer) in results.iter_mut() {
+        if let Some(buffer) = buffer.take() {
+            stdout.print(&buffer)?;
+        }
+    }
+    report_skipped(&skipped);
+    Ok(results.into_iter().map(|(_, count, _)| count).collect())
 }
 
 pub fn get_random_text(dir_path: &Path, settings: &Settings, snippet_length: usize) -> Result<(Vec<String>, String), BoxError> {
     
     let mut rng = rand::thread_rng();
-    let pdf_files = utils::get_folder_files(dir_path);
+    let pdf_files = utils::get_folder_files(dir_path, &settings.walk)?;
+    if pdf_files.is_empty() {
+        return Err(format!("No pdf files in {}", dir_path.display()).into());
+    }
 
     let file_index = rng.gen_range(0..pdf_files.len());
     let file = Path::new(&pdf_files[file_index].1);
-    let text = settings.tools.pdf_text(file)?;
+    let text = settings.tools.pdf_text_pages(file, settings.page_ranges.as_ref())?;
 
+    let text_str = String::from_utf8_lossy(text.as_slice()).to_string();
+    let pages = settings.tools.split_pages(&text_str)?;
     if text.len() < snippet_length {
-        let slice = String::from_utf8_lossy(text.as_slice()).to_string();
+        // a range backend pads the pages before the selection, the pages after it can be there as well
+        let slice = (0..pages.len()).filter(|page| settings.page_selected(*page)).map(|page| pages[page]).collect::<Vec<_>>().join("\u{c}");
         
         Ok((vec![file.display().to_string()], slice))
     } else {
-        let text_str = String::from_utf8_lossy(text.as_slice()).to_string();
-        let pages = settings.tools.split_pages(&text_str)?;
-        let page_index = rng.gen_range(0..pages.len());
+        let selected: Vec<usize> = (0..pages.len()).filter(|page| settings.page_selected(*page) && pages[*page].len() > snippet_length + 1).collect();
+        if selected.is_empty() {
+            return Err(format!("No selected page of {} is longer than {} bytes", file.display(), snippet_length).into());
+        }
+        let page_index = selected[rng.gen_range(0..selected.len())];
         let page_text = pages[page_index];
         let start = rng.gen_range(0..page_text.len() - snippet_length -1);
         
         let left_index = utils::get_left_index_trim(&page_text, start, 1);
         let right_index = utils::get_right_index_trim(&page_text, start+snippet_length, 1);
+        // the trim looks at bytes, move both ends out of a multibyte character
+        let left_index = (left_index..page_text.len()).find(|i| page_text.is_char_boundary(*i)).unwrap_or(page_text.len());
+        let right_index = (right_index..=page_text.len()).find(|i| page_text.is_char_boundary(*i)).unwrap_or(page_text.len()).max(left_index);
         let slice = &page_text[left_index..right_index];
 
-        let meta = vec![file.display().to_string(), format!("Page: {}", page_index), format!("range: {left_index}-{right_index}")];
+        let meta = vec![file.display().to_string(), format!("Page: {}", page_index + 1), format!("range: {left_index}-{right_index}")];
         //let slice = &text[start..start+snippet_length];
         //let result = String::from_utf8_lossy(slice).to_string();
         Ok((meta, slice.to_string()))
@@ -235,6 +629,15 @@ fn test_s_len() {
     assert_eq!(sparkle_heart_vec.len(), sparkle_heart.len());
 }
 
+#[test]
+fn test_pdf_extract_missing_file() {
+    let tools = PdfExtractWrapper {};
+    let file = Path::new("./data/does_not_exist.pdf");
+
+    assert!(tools.pdf_text(file).is_err());
+    assert!(tools.pdf_info(file).is_err());
+}
+
 // pub fn search_file(file_path: &Path, pattern: &String, settings: &Settings) {
    
 
@@ -255,4 +658,18 @@ fn test_s_len() {
 //         },
 //     }
     
-// }
\ No newline at end of file
+// }
+#[test]
+fn test_parse_pdf_date() {
+    assert_eq!(parse_pdf_date("Tue Mar  5 10:12:01 2024 CET"), Some(20240305101201));
+    assert_eq!(parse_pdf_date("D:20240305101201+01'00'"), Some(20240305101201));
+    assert_eq!(parse_pdf_date("D:2023"), Some(20230101000000));
+    assert_eq!(parse_pdf_date("2024-03-05T10:12:01+01"), Some(20240305101201));
+    assert_eq!(parse_pdf_date(""), None);
+
+    let row = |date: &str| InfoRow { file: String::new(), info: BTreeMap::from([("CreationDate".into(), Some(date.into()))]), size: 0 };
+    let (old, new, unknown) = (row("Wed Jan 10 08:00:00 2024"), row("D:20240305"), row("yesterday"));
+    assert_eq!(old.compare(&new, &InfoColumn::Created), Ordering::Less);
+    assert_eq!(unknown.compare(&old, &InfoColumn::Created), Ordering::Greater);
+    assert_eq!(new.compare(&unknown, &InfoColumn::Created), Ordering::Less);
+}
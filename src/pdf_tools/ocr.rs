@@ -0,0 +1,143 @@
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}, process::Command, time::Duration};
+
+use rand::Rng;
+
+use crate::{utils::PageRanges, BoxError};
+
+use super::{limit, PDFTools};
+
+/// Renders pages with `pdftoppm` and reads them back with a local `tesseract`.
+#[derive(Debug)]
+pub struct OcrTools {
+    pub pdftoppm: PathBuf,
+    pub tesseract: PathBuf,
+    pub language: String,
+    /// `--timeout` of each pdftoppm or tesseract process
+    pub timeout: Option<Duration>,
+}
+
+impl OcrTools {
+    pub fn is_available(&self) -> bool {
+        Command::new(&self.tesseract).arg("--version").output().is_ok()
+    }
+
+    /// OCR text of a single page, `page` is 1-based.
+    pub fn ocr_page(&self, file_path: &Path, page: usize) -> Result<String, BoxError> {
+        let work_dir = std::env::temp_dir().join(format!("pdf_grep_ocr_{}_{}", std::process::id(), rand::thread_rng().gen::<u32>()));
+        fs::create_dir_all(&work_dir)?;
+
+        let result = self.render_and_read(file_path, page, &work_dir);
+        let _ = fs::remove_dir_all(&work_dir);
+        result
+    }
+
+    fn render_and_read(&self, file_path: &Path, page: usize, work_dir: &Path) -> Result<String, BoxError> {
+        let page_arg = page.to_string();
+        let output = limit::run_command("pdftoppm", Command::new(&self.pdftoppm)
+            .args(["-r", "300", "-png", "-f", &page_arg, "-l", &page_arg])
+            .arg(file_path)
+            .arg(work_dir.join("page")), self.timeout)?;
+        if !output.status.success() {
+            return Err(format!("pdftoppm failed ({}):\n{}", output.status, String::from_utf8_lossy(&output.stderr)).into());
+        }
+
+        // xpdf and poppler pad the page number in the image name differently
+        let image = fs::read_dir(work_dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "png"))
+            .ok_or_else(|| format!("pdftoppm created no image for page {}", page))?;
+
+        let output = limit::run_command("tesseract", Command::new(&self.tesseract)
+            .arg(&image)
+            .arg("stdout")
+            .args(["-l", &self.language]), self.timeout)?;
+        if !output.status.success() {
+            return Err(format!("tesseract failed ({}):\n{}", output.status, String::from_utf8_lossy(&output.stderr)).into());
+        }
+
+        // tesseract ends every page with a form feed, the page split is ours
+        Ok(String::from_utf8_lossy(&output.stdout).replace('\u{c}', ""))
+    }
+}
+
+/// Runs OCR on the pages where the wrapped backend found no text, or on all pages when `force` is set.
+#[derive(Debug)]
+pub struct OcrWrapper {
+    pub tools: Box<dyn PDFTools + std::marker::Send + std::marker::Sync>,
+    pub ocr: OcrTools,
+    pub force: bool,
+}
+
+impl PDFTools for OcrWrapper {
+    fn pdf_info(&self, file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError> {
+        self.tools.pdf_info(file_path)
+    }
+
+    fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError> {
+        let content = self.tools.pdf_text(file_path)?;
+        self.ocr_pages(file_path, content, None)
+    }
+
+    fn pdf_text_range(&self, file_path: &Path, first: usize, last: Option<usize>) -> Result<Vec<u8>, BoxError> {
+        let content = self.tools.pdf_text_range(file_path, first, last)?;
+        self.ocr_pages(file_path, content, Some(&PageRanges(vec![(first, last)])))
+    }
+
+    /// Only the pages of the selection are OCRed, not the gaps in its range.
+    fn pdf_text_pages(&self, file_path: &Path, pages: Option<&PageRanges>) -> Result<Vec<u8>, BoxError> {
+        let content = self.tools.pdf_text_pages(file_path, pages)?;
+        self.ocr_pages(file_path, content, pages)
+    }
+
+    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
+        self.tools.split_pages(text)
+    }
+}
+
+impl OcrWrapper {
+    /// Replaces the selected pages of `content`, all without a selection, with their OCR text where needed.
+    fn ocr_pages(&self, file_path: &Path, content: Vec<u8>, pages: Option<&PageRanges>) -> Result<Vec<u8>, BoxError> {
+        let in_range = |page: usize| pages.map_or(true, |pages| pages.contains(page + 1));
+        let text = String::from_utf8_lossy(&content);
+        let mut page_texts = self.tools.split_pages(&text)?;
+        // the text output ends with a form feed after the last page
+        let trailing = page_texts.len() > 1 && page_texts.last().is_some_and(|p| p.is_empty());
+        if trailing {
+            page_texts.pop();
+        }
+
+        if !self.force && page_texts.iter().enumerate().all(|(page, p)| !in_range(page) || !p.trim().is_empty()) {
+            return Ok(content);
+        }
+
+        let mut result = Vec::with_capacity(page_texts.len());
+        for (page, page_text) in page_texts.iter().enumerate() {
+            if in_range(page) && (self.force || page_text.trim().is_empty()) {
+                result.push(self.ocr.ocr_page(file_path, page + 1)?);
+            } else {
+                result.push(page_text.to_string());
+            }
+        }
+        let mut joined = result.join("\u{c}");
+        if trailing {
+            joined.push('\u{c}');
+        }
+
+        Ok(joined.into_bytes())
+    }
+}
+
+#[test]
+fn test_ocr_skipped_for_text_pages() {
+    use super::PdfDummyTool;
+
+    let tools = OcrWrapper {
+        tools: Box::new(PdfDummyTool {}),
+        ocr: OcrTools { pdftoppm: "no_pdftoppm".into(), tesseract: "no_tesseract".into(), language: "eng".into(), timeout: None },
+        force: false,
+    };
+    // the dummy text has content, so no OCR binary is ever called
+    let text = tools.pdf_text(Path::new("dummy.pdf")).unwrap();
+    assert!(String::from_utf8_lossy(&text).contains("test wrapper"));
+}
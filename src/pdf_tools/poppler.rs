@@ -0,0 +1,96 @@
+use std::{collections::BTreeMap, ffi::OsStr, path::{Path, PathBuf}, process::Command, time::Duration};
+
+use crate::{utils, BoxError};
+
+use super::{limit, PDFTools};
+
+/// Backend for the poppler-utils `pdftotext`/`pdfinfo` binaries.
+/// Uses the binaries from `tools_folder` if they exist there, otherwise relies on `$PATH`.
+#[derive(Debug)]
+pub struct PopplerWrapper {
+    pub tools_folder: Option<PathBuf>,
+    /// `--timeout` of each pdftotext or pdfinfo process
+    pub timeout: Option<Duration>,
+}
+
+impl PopplerWrapper {
+    pub fn tool_path(&self, name: &str) -> PathBuf {
+        utils::find_tool(&[self.tools_folder.as_deref()], name)
+    }
+
+    pub fn is_available(&self) -> bool {
+        Command::new(self.tool_path("pdftotext")).arg("-v").output().is_ok()
+    }
+
+    /// Number of pages in the file, from the `Pages` line of pdfinfo.
+    fn page_count(&self, file_path: &Path) -> Option<usize> {
+        self.pdf_info(file_path).ok()?.get("Pages")?.as_deref()?.parse().ok()
+    }
+
+    fn run(&self, name: &str, args: &[&OsStr]) -> Result<Vec<u8>, BoxError> {
+        let output = limit::run_command(name, Command::new(self.tool_path(name)).args(args), self.timeout)?;
+
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(format!("{} failed ({}):\n{}", name, output.status, String::from_utf8_lossy(&output.stderr)).into())
+        }
+    }
+}
+
+/// Parses the `Key:   value` lines printed by pdfinfo.
+pub fn parse_pdfinfo(output: &str) -> BTreeMap<String, Option<String>> {
+    output.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| {
+            let value = value.trim();
+            (key.trim().to_string(), if value.is_empty() { None } else { Some(value.to_string()) })
+        })
+        .collect()
+}
+
+impl PDFTools for PopplerWrapper {
+    fn pdf_info(&self, file_path: &Path) -> Result<BTreeMap<String, Option<String>>, BoxError> {
+        let output = self.run("pdfinfo", &["-enc".as_ref(), "UTF-8".as_ref(), file_path.as_os_str()])?;
+        Ok(parse_pdfinfo(&String::from_utf8_lossy(&output)))
+    }
+
+    fn pdf_text(&self, file_path: &Path) -> Result<Vec<u8>, BoxError> {
+        self.run("pdftotext", &["-enc".as_ref(), "UTF-8".as_ref(), file_path.as_os_str(), "-".as_ref()])
+    }
+
+    fn pdf_text_range(&self, file_path: &Path, first: usize, last: Option<usize>) -> Result<Vec<u8>, BoxError> {
+        let first_arg = first.to_string();
+        let last_arg = last.map(|last| last.to_string());
+        let mut args = vec![OsStr::new("-enc"), OsStr::new("UTF-8"), OsStr::new("-f"), OsStr::new(&first_arg)];
+        if let Some(last_arg) = &last_arg {
+            args.extend([OsStr::new("-l"), OsStr::new(last_arg)]);
+        }
+        args.extend([file_path.as_os_str(), OsStr::new("-")]);
+
+        let output = match self.run("pdftotext", &args) {
+            Ok(output) => output,
+            // pdftotext fails for a first page after the end, the file just has no text in the range
+            Err(_) if first > 1 && self.page_count(file_path).is_some_and(|pages| first > pages) => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let mut text = "\u{c}".repeat(first - 1).into_bytes();
+        text.extend(output);
+        Ok(text)
+    }
+
+    fn split_pages<'a>(&self, text: &'a str) -> Result<Vec<&'a str>, BoxError> {
+        Ok(text.split('\u{c}').collect::<Vec<_>>())
+    }
+}
+
+#[test]
+fn test_parse_pdfinfo() {
+    let output = "Title:          Dubliners\nAuthor:         \nCreationDate:   Tue Mar  5 10:12:01 2024 CET\nPages:          152\n";
+    let info = parse_pdfinfo(output);
+
+    assert_eq!(info.get("Title"), Some(&Some("Dubliners".to_string())));
+    assert_eq!(info.get("Author"), Some(&None));
+    assert_eq!(info.get("CreationDate"), Some(&Some("Tue Mar  5 10:12:01 2024 CET".to_string())));
+    assert_eq!(info.get("Pages"), Some(&Some("152".to_string())));
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeSet;
+
+use grep::matcher::{Match, Matcher};
+
+use crate::{normalize::{self, FoldedPage, FoldingMatcher}, settings::{phrase_pattern, QueryScope, Settings}, BoxError};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Near(usize),
+    Open,
+    Close,
+}
+
+/// Operators are only recognized in upper case, "and" is an ordinary word.
+fn tokenize(query: &str) -> Result<Vec<Token>, BoxError> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => { chars.next(); },
+            '(' => { chars.next(); tokens.push(Token::Open); },
+            ')' => { chars.next(); tokens.push(Token::Close); },
+            '"' => {
+                chars.next();
+                let phrase: String = chars.by_ref().take_while(|c| *c != '"').collect();
+                if phrase.trim().is_empty() {
+                    return Err("empty phrase in query".into());
+                }
+                tokens.push(Token::Phrase(phrase));
+            },
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace() && !"()\"".contains(**c)) {
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => match word.strip_prefix("NEAR/") {
+                        Some(n) => Token::Near(n.parse().map_err(|_| format!("NEAR needs a word distance like NEAR/5, not {}", word))?),
+                        None => Token::Word(word),
+                    },
+                });
+            },
+        }
+    }
+    Ok(tokens)
+}
+
+/// Query tree, a term is the index of its matcher in the `Query`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Term(usize),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// Both terms with at most `n` words between them
+    Near(usize, usize, usize),
+}
+
+/// A word of the query matches whole words, like the words of the index. There is no `\b` on a
+/// side that ends in a non-word character, "C++" would never match then.
+fn word_pattern(word: &str) -> String {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let start = if is_word(word.chars().next()) { r"\b" } else { "" };
+    let end = if is_word(word.chars().last()) { r"\b" } else { "" };
+    format!("{}{}{}", start, regex::escape(word), end)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Patterns of the terms, in the order of `Expr::Term`
+    terms: Vec<String>,
+    negated: Vec<bool>,
+    in_not: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn push_term(&mut self, pattern: String) -> Expr {
+        self.terms.push(pattern);
+        self.negated.push(self.in_not % 2 == 1);
+        Expr::Term(self.terms.len() - 1)
+    }
+
+    // or := and (OR and)*
+    fn parse_or(&mut self) -> Result<Expr, BoxError> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    // and := not (AND? not)*, two terms side by side are an AND
+    fn parse_and(&mut self) -> Result<Expr, BoxError> {
+        let mut expr = self.parse_not()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => { self.next(); },
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open) => (),
+                _ => break,
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    // not := NOT not | near
+    fn parse_not(&mut self) -> Result<Expr, BoxError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            self.in_not += 1;
+            let expr = self.parse_not();
+            self.in_not -= 1;
+            return Ok(Expr::Not(Box::new(expr?)));
+        }
+        self.parse_near()
+    }
+
+    // near := primary (NEAR/n term)*, "a NEAR/5 b NEAR/5 c" needs both pairs
+    fn parse_near(&mut self) -> Result<Expr, BoxError> {
+        let mut expr = self.parse_primary()?;
+        let mut last = expr.clone();
+        while let Some(Token::Near(n)) = self.peek().cloned() {
+            self.next();
+            let right = self.parse_primary()?;
+            let (Expr::Term(a), Expr::Term(b)) = (&last, &right) else {
+                return Err("NEAR only joins words and phrases".into());
+            };
+            let near = Expr::Near(*a, *b, n);
+            expr = if expr == last { near } else { Expr::And(Box::new(expr), Box::new(near)) };
+            last = right;
+        }
+        Ok(expr)
+    }
+
+    // primary := word | "phrase" | ( or )
+    fn parse_primary(&mut self) -> Result<Expr, BoxError> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(self.push_term(word_pattern(&word))),
+            // terms are literal, whitespace in a phrase matches any whitespace
+            Some(Token::Phrase(phrase)) => Ok(self.push_term(phrase_pattern(&phrase, true))),
+            Some(Token::Open) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing ) in query".into()),
+                }
+            },
+            Some(token) => Err(format!("unexpected {:?} in query", token).into()),
+            None => Err("query ends too early".into()),
+        }
+    }
+}
+
+/// Parsed query with a matcher per term, see `Search --query`.
+#[derive(Debug)]
+pub struct Query {
+    pub expr: Expr,
+    pub scope: QueryScope,
+    matchers: Vec<FoldingMatcher>,
+    /// Matches every term that is not under a NOT, for the printer
+    pub highlight: FoldingMatcher,
+}
+
+impl Query {
+    pub fn parse(query: &str, settings: &Settings) -> Result<Query, BoxError> {
+        let mut parser = Parser { tokens: tokenize(query)?, pos: 0, terms: vec![], negated: vec![], in_not: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {:?} in query", token).into());
+        }
+
+        let patterns = parser.terms;
+        let matchers = patterns.iter()
+            .map(|pattern| settings.build_matcher(pattern, false, true))
+            .collect::<Result<Vec<_>, _>>()?;
+        let positive: Vec<&str> = patterns.iter().zip(&parser.negated).filter(|(_, negated)| !**negated).map(|(p, _)| p.as_str()).collect();
+        // a query of only NOT terms has nothing to highlight
+        let highlight = if positive.is_empty() { r"\b\B".to_string() } else { positive.join("|") };
+
+        Ok(Query {
+            expr,
+            scope: settings.query_scope,
+            matchers,
+            highlight: settings.build_matcher(&highlight, false, true)?,
+        })
+    }
+
+    /// Whether the text satisfies the query.
+    pub fn is_match(&self, text: &str) -> bool {
+        let folded = FoldedPage::new(text, &self.highlight.options);
+        let hits: Vec<Vec<Match>> = self.matchers.iter().map(|matcher| {
+            let mut found = vec![];
+            let _ = folded.matcher(matcher).find_iter(text.as_bytes(), |m| { found.push(m); true });
+            found
+        }).collect();
+        let word_starts: Vec<usize> = text.char_indices()
+            .filter(|(i, c)| !c.is_whitespace() && (*i == 0 || text[..*i].ends_with(char::is_whitespace)))
+            .map(|(i, _)| i)
+            .collect();
+        eval(&self.expr, &hits, &word_starts)
+    }
+
+    /// 0-based indices of the pages to print: the pages matching the query, or with the document
+    /// scope all pages with a hit when the whole document matches.
+    pub fn matching_pages(&self, pages: &[&str], settings: &Settings) -> BTreeSet<usize> {
+        let pages: Vec<_> = pages.iter().map(|page| normalize::normalize_page(page, settings)).collect();
+        match self.scope {
+            QueryScope::Page => (0..pages.len()).filter(|page| self.is_match(&pages[*page])).collect(),
+            QueryScope::Document => {
+                let text = pages.iter().map(|page| page.as_ref()).collect::<Vec<_>>().join("\n");
+                if !self.is_match(&text) {
+                    return BTreeSet::new();
+                }
+                (0..pages.len()).filter(|page| self.highlight.is_match(pages[*page].as_bytes()).unwrap_or(false)).collect()
+            },
+        }
+    }
+}
+
+fn eval(expr: &Expr, hits: &[Vec<Match>], word_starts: &[usize]) -> bool {
+    match expr {
+        Expr::Term(term) => !hits[*term].is_empty(),
+        Expr::And(a, b) => eval(a, hits, word_starts) && eval(b, hits, word_starts),
+        Expr::Or(a, b) => eval(a, hits, word_starts) || eval(b, hits, word_starts),
+        Expr::Not(a) => !eval(a, hits, word_starts),
+        Expr::Near(a, b, n) => {
+            let word = |offset: usize| word_starts.partition_point(|start| *start <= offset).saturating_sub(1);
+            hits[*a].iter().any(|ma| hits[*b].iter().any(|mb| {
+                let (first, second) = if ma.start() <= mb.start() { (ma, mb) } else { (mb, ma) };
+                // words between the end of the first term and the start of the second
+                word(second.start()).saturating_sub(word(first.end().saturating_sub(1))) <= n + 1
+            }))
+        },
+    }
+}
+
+#[test]
+fn test_query() {
+    let settings = Settings::default();
+    let query = Query::parse(r#"(quantum OR "string theory") AND NOT gravity"#, &settings).unwrap();
+    assert!(query.is_match("Quantum mechanics"));
+    assert!(query.is_match("a string\ntheory of fields"));
+    assert!(!query.is_match("quantum gravity"));
+    assert!(!query.is_match("quantumness"));
+    assert!(Query::parse("C++", &settings).unwrap().is_match("written in C++ code"));
+
+    let near = Query::parse("field NEAR/2 theory", &settings).unwrap();
+    assert!(near.is_match("field of a theory"));
+    assert!(!near.is_match("field is not a good theory"));
+
+    assert!(Query::parse("(quantum", &settings).is_err());
+    assert!(Query::parse("a NEAR/x b", &settings).is_err());
+}
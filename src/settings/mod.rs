@@ -1,14 +1,21 @@
 use std::io::{self, IsTerminal};
 use std::path::PathBuf;
+use std::time::Duration;
 
-use grep::printer::{ColorSpecs, Standard, StandardBuilder, Summary, SummaryBuilder};
+use grep::printer::{ColorSpecs, Standard, StandardBuilder, Summary, SummaryBuilder, SummaryKind};
+use grep::regex::RegexMatcherBuilder;
 use grep::searcher::{BinaryDetection, Searcher, SearcherBuilder};
 use serde::{Deserialize, Serialize};
-use termcolor::{BufferedStandardStream, ColorChoice, WriteColor};
+use termcolor::{BufferWriter, BufferedStandardStream, ColorChoice, WriteColor};
 use termcolor::{Color, ColorSpec};
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use crate::fuzzy::FuzzyMatcher;
+use crate::normalize::{self, FoldOptions, FoldingMatcher};
 use crate::pdf_tools::{AvailablePdfTools, PDFTools, PdfDummyTool};
+use crate::patterns::PatternSet;
+use crate::query::Query;
+use crate::utils::{FileSize, PageRanges, WalkOptions};
 
 use self::toml_settings::TomlSettings;
 
@@ -21,28 +28,269 @@ pub struct Cli {
     #[arg(short, long, group = "input")]
     pub file: Option<PathBuf>,
 
+    /// Only search the files of the directory matching GLOB, !GLOB leaves them out, can be given more than once.
+    /// Files in .gitignore, .ignore, the git excludes and .pdfgrepignore are left out unless GLOB matches them
+    #[arg(short, long, value_name = "GLOB")]
+    pub glob: Vec<String>,
+
+    /// Like --glob, but ignores case
+    #[arg(long, value_name = "GLOB")]
+    pub iglob: Vec<String>,
+
+    /// Don't go more than NUM folders deep into the directory
+    #[arg(long, value_name = "NUM")]
+    pub max_depth: Option<usize>,
+
+    /// Also search hidden files and folders
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// Follow symbolic links
+    #[arg(long)]
+    pub follow: bool,
+
+    /// Also search the files in .gitignore, .ignore and the git excludes, .pdfgrepignore still applies
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Also search files with the extension EXT as pdf files, e.g. ai
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    pub type_add: Vec<String>,
+
+    /// Also search files with another extension or none when they start with a %PDF- header
+    #[arg(long)]
+    pub sniff: bool,
+
     #[command(subcommand)]
     pub command: Actions,
 
     #[arg(short, long)]
     pub xpdf_path: Option<PathBuf>,
+
+    /// OCR every page with tesseract, not only pages without text
+    #[arg(long)]
+    pub ocr: bool,
+
+    /// Always extract the text, don't use or fill the text cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Search the text as extracted, don't rejoin words hyphenated at a line end
+    #[arg(long)]
+    pub no_dehyphenate: bool,
+
+    /// Ignore accents, "naive" also finds "naïve"
+    #[arg(long)]
+    pub fold_accents: bool,
+
+    /// Number of threads for a directory search, the default is one per CPU
+    #[arg(short = 'j', long, value_name = "N")]
+    pub threads: Option<usize>,
+
+    /// Work split of a directory search: a thread per file, or per folder with its files in order
+    #[arg(long, value_enum, default_value = "thread-per-file")]
+    pub folder_mode: FolderSearchMode,
+
+    /// Skip a file when its pdftotext, pdfinfo and OCR processes take more than SECS seconds together, pdf-extract runs in the process and can't be stopped
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Skip files larger than SIZE, e.g. 500K or 20M
+    #[arg(long, value_name = "SIZE")]
+    pub max_filesize: Option<FileSize>,
+
+    /// Skip files with more than N pages
+    #[arg(long, value_name = "N")]
+    pub max_pages: Option<usize>,
+
+    /// Print diagnostics like the config file and the xpdf version to stderr
+    #[arg(short, long)]
+    pub verbose: bool,
 }
 
 #[derive(Debug, Subcommand, Clone)]
 pub enum Actions {
-    Search { pattern: String },
-    Info,
+    Search {
+        #[arg(required_unless_present_any = ["regexp", "pattern_file"])]
+        pattern: Option<String>,
+        /// Also search for PATTERN, can be given more than once
+        #[arg(short = 'e', long, value_name = "PATTERN")]
+        regexp: Vec<String>,
+        /// Also search for the patterns in FILE, one per line
+        #[arg(short = 'f', long, value_name = "FILE")]
+        pattern_file: Vec<PathBuf>,
+        /// Show the pattern that found each match
+        #[arg(long)]
+        tag: bool,
+        /// Find the patterns as literal text with up to N wrong, missing or extra characters, e.g. from OCR
+        #[arg(long, value_name = "N", conflicts_with_all = ["query", "word_regexp", "line_regexp"])]
+        fuzzy: Option<usize>,
+        /// Print results for people (standard) or as JSON Lines for other tools (json). The JSON line numbers
+        /// and byte offsets count in the page text as it is searched, after dehyphenation and with the overlap of the next page
+        #[arg(long, value_enum, default_value = "standard")]
+        output: OutputMode,
+        /// Search case sensitively, the default ignores case
+        #[arg(short = 's', long, conflicts_with = "smart_case")]
+        case_sensitive: bool,
+        /// Ignore case unless the pattern has an uppercase character
+        #[arg(short = 'S', long)]
+        smart_case: bool,
+        /// Treat the pattern as a literal string instead of a regex
+        #[arg(short = 'F', long)]
+        fixed_strings: bool,
+        /// Only match whole words
+        #[arg(short, long)]
+        word_regexp: bool,
+        /// Only match whole lines
+        #[arg(short = 'x', long)]
+        line_regexp: bool,
+        /// Let whitespace in the pattern match any whitespace, line breaks included
+        #[arg(short = 'U', long, visible_alias = "phrase")]
+        multiline: bool,
+        /// Also search the first LINES lines of the next page, so a --multiline phrase can run over a page break
+        #[arg(long, value_name = "LINES", default_value_t = 0)]
+        page_overlap: usize,
+        /// Only search these pages, e.g. 10-40,55,100-
+        #[arg(long)]
+        pages: Option<PageRanges>,
+        /// Also show the printed page label of each match, e.g. "Page: 23 (ix)"
+        #[arg(long)]
+        page_labels: bool,
+        /// Read the pattern as a query: words, "phrases", AND, OR, NOT, NEAR/n and parentheses
+        #[arg(short = 'Q', long, requires = "pattern", conflicts_with_all = ["regexp", "pattern_file"])]
+        query: bool,
+        /// Where all parts of a --query must match
+        #[arg(long, value_enum, default_value = "page", requires = "query")]
+        scope: QueryScope,
+        /// Only print the paths of the files with a match
+        #[arg(short = 'l', long, group = "summary")]
+        files_with_matches: bool,
+        /// Only print the paths of the files without a match
+        #[arg(short = 'L', long, group = "summary")]
+        files_without_match: bool,
+        /// Only print the number of matches of each file, or with --count=page of each page
+        #[arg(short = 'c', long, value_enum, value_name = "PER", num_args = 0..=1, default_missing_value = "file", require_equals = true, group = "summary")]
+        count: Option<CountMode>,
+        /// Print nothing, the exit status tells whether anything matched
+        #[arg(short = 'q', long, group = "summary")]
+        quiet: bool,
+        /// Order of the files of a directory search, all but none wait for the last file before printing
+        #[arg(long, value_enum, default_value = "none")]
+        sort: SortMode,
+        /// Show NUM lines after each match, never past the end of the page
+        #[arg(short = 'A', long, value_name = "NUM")]
+        after_context: Option<usize>,
+        /// Show NUM lines before each match, never before the start of the page
+        #[arg(short = 'B', long, value_name = "NUM")]
+        before_context: Option<usize>,
+        /// Show NUM lines before and after each match
+        #[arg(short = 'C', long, value_name = "NUM")]
+        context: Option<usize>,
+    },
+    Info {
+        /// Columns of the directory table
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "file,title,author,pages,producer,created,size")]
+        columns: Vec<InfoColumn>,
+        /// Sort the directory table by these columns
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "file")]
+        sort: Vec<InfoColumn>,
+    },
     Test,
-    Text,
-    Rand { length: Option<usize> },
+    Text {
+        /// Only print these pages, e.g. 10-40,55,100-
+        #[arg(long)]
+        pages: Option<PageRanges>,
+    },
+    Rand {
+        length: Option<usize>,
+        /// Only pick the snippet from these pages, e.g. 10-40,55,100-
+        #[arg(long)]
+        pages: Option<PageRanges>,
+    },
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    Index {
+        #[command(subcommand)]
+        action: IndexAction,
+    },
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaseMode {
+    Insensitive,
+    Sensitive,
+    Smart,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortMode {
+    /// As the files are done
+    None,
+    Path,
+    /// Last modified last
+    Mtime,
+    /// Most matches first
+    Matches,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CountMode {
+    File,
+    Page,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum QueryScope {
+    Page,
+    Document,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputMode {
+    Standard,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum InfoColumn {
+    File,
+    Title,
+    Author,
+    Pages,
+    Producer,
+    Created,
+    Size,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum CacheAction {
+    /// Number of cached files and size of the cached text
+    Stats,
+    /// Remove the entries of deleted files
+    Prune,
+    /// Remove all cached text
+    Clear,
+}
 
+#[derive(Debug, Subcommand, Clone)]
+pub enum IndexAction {
+    /// Create or update the index of the directory, only new and changed files are extracted
+    Build,
+    /// Search the words of the query in the index, all words must be on the same page
+    Search { query: String },
 }
-#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize, ValueEnum)]
 pub enum FolderSearchMode {
     ThreadPerFolder,
     ThreadPerFile,
 }
 
+/// Longer lines are cut in the search output, with a preview of the rest.
+pub const MAX_COLUMNS: usize = 750;
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ShortenLineMode {
     None,
@@ -67,10 +315,48 @@ pub struct Settings {
     pub print_text: bool,
     pub shorten_line_mode: ShortenLineMode,
     pub color_choice: ColorChoice,
+    pub output_mode: OutputMode,
+    pub case_mode: CaseMode,
+    pub fixed_strings: bool,
+    pub word_regexp: bool,
+    pub line_regexp: bool,
+    pub multiline: bool,
+    pub page_overlap: usize,
+    pub page_ranges: Option<PageRanges>,
+    pub page_labels: bool,
+    pub query_scope: QueryScope,
+    /// Parsed pattern of `Search --query`, set in main
+    pub query: Option<Query>,
+    /// All patterns of a search without `--query`, set in main
+    pub patterns: Option<PatternSet>,
+    pub tag_patterns: bool,
+    /// Edit distance of `--fuzzy`
+    pub fuzzy: Option<usize>,
+    /// Output of -l, -L, -c or -q instead of the matching lines
+    pub summary_kind: Option<SummaryKind>,
+    /// `--count=page`
+    pub count_pages: bool,
+    pub sort_mode: SortMode,
+    pub verbose: bool,
+    pub before_context: usize,
+    pub after_context: usize,
 
     pub cli: Option<Cli>,
     pub use_pdf_tool: AvailablePdfTools,
+    pub pdf_tool_priority: Vec<AvailablePdfTools>,
     pub xpdf_tools_folder: Option<PathBuf>,
+    pub poppler_tools_folder: Option<PathBuf>,
+    pub tesseract_path: Option<PathBuf>,
+    pub ocr_language: String,
+    pub use_text_cache: bool,
+    /// Backend calls running at once, 0 for no limit
+    pub max_parallel_extractions: usize,
+    pub timeout: Option<Duration>,
+    pub max_filesize: Option<u64>,
+    pub max_pages: Option<usize>,
+    pub walk: WalkOptions,
+    pub dehyphenate: bool,
+    pub fold_options: FoldOptions,
     
 }
 
@@ -78,25 +364,86 @@ impl Settings {
     pub fn new() -> Self {
         let mut settings = Settings::default();
 
-        Settings::merge_toml_settings(&mut settings);
         if cfg!(not(test)) {
-            println!("Parse cli - not testing");
             settings.cli = Some(Cli::parse());
         }
-        
+        settings.verbose = settings.cli.as_ref().is_some_and(|cli| cli.verbose);
+        Settings::merge_toml_settings(&mut settings);
+        Settings::merge_cli_settings(&mut settings);
+
         settings
     }
 
+    fn merge_cli_settings(settings: &mut Settings) {
+        let Some(cli) = settings.cli.as_ref() else { return };
+
+        settings.folder_search_mode = cli.folder_mode;
+        settings.timeout = cli.timeout.map(Duration::from_secs);
+        settings.max_filesize = cli.max_filesize.map(|size| size.0);
+        settings.max_pages = cli.max_pages;
+        settings.walk = WalkOptions {
+            globs: cli.glob.clone(),
+            iglobs: cli.iglob.clone(),
+            max_depth: cli.max_depth,
+            hidden: cli.hidden,
+            follow: cli.follow,
+            no_ignore: cli.no_ignore,
+            // "ai", ".ai" and "*.ai" all name the extension
+            extensions: cli.type_add.iter().map(|ext| ext.trim_start_matches('*').trim_start_matches('.').to_string()).collect(),
+            sniff: cli.sniff,
+        };
+        settings.dehyphenate = settings.dehyphenate && !cli.no_dehyphenate;
+        settings.fold_options.accents = settings.fold_options.accents || cli.fold_accents;
+        if let Actions::Search {pages, ..} | Actions::Text {pages} | Actions::Rand {pages, ..} = &cli.command {
+            settings.page_ranges = pages.clone();
+        }
+
+        if let Actions::Search {output, case_sensitive, smart_case, fixed_strings, word_regexp, line_regexp, multiline, page_overlap, page_labels, query, scope, tag, fuzzy, files_with_matches, files_without_match, count, quiet, sort, after_context, before_context, context, ..} = &cli.command {
+            settings.output_mode = *output;
+            settings.case_mode = if *case_sensitive {
+                CaseMode::Sensitive
+            } else if *smart_case {
+                CaseMode::Smart
+            } else {
+                CaseMode::Insensitive
+            };
+            settings.fixed_strings = *fixed_strings;
+            settings.word_regexp = *word_regexp;
+            settings.line_regexp = *line_regexp;
+            // query phrases may run over line breaks
+            settings.multiline = *multiline || *query;
+            settings.page_overlap = *page_overlap;
+            settings.page_labels = *page_labels;
+            settings.query_scope = *scope;
+            settings.tag_patterns = *tag;
+            settings.fuzzy = *fuzzy;
+            settings.summary_kind = if *files_with_matches {
+                Some(SummaryKind::PathWithMatch)
+            } else if *files_without_match {
+                Some(SummaryKind::PathWithoutMatch)
+            } else if *quiet {
+                Some(SummaryKind::Quiet)
+            } else {
+                count.map(|_| SummaryKind::CountMatches)
+            };
+            settings.count_pages = *count == Some(CountMode::Page);
+            settings.sort_mode = *sort;
+            // -A and -B win over -C
+            settings.before_context = before_context.or(*context).unwrap_or(0);
+            settings.after_context = after_context.or(*context).unwrap_or(0);
+        }
+    }
+
     fn merge_toml_settings(settings: &mut Settings) {
 
         let toml: TomlSettings;
 
-        match TomlSettings::load() {
+        match TomlSettings::load(settings.verbose) {
             Ok(toml_loaded) => toml = toml_loaded,
 
             Err(e) => {
                 if let Some(_e) = e.downcast_ref::<io::Error>() {
-                    toml = TomlSettings::create_default().unwrap();
+                    toml = TomlSettings::create_default(settings.verbose).unwrap();
                 } else {
                     panic!("{:?}", e.to_string());
                 }
@@ -106,7 +453,20 @@ impl Settings {
         settings.search_color_specs = ColorSpecs::new(&toml.make_color_specs());
         settings.info_color_spec = toml.get_info_color_spec();
         settings.use_pdf_tool = toml.use_pdf_tool;
+        settings.pdf_tool_priority = toml.pdf_tool_priority;
         settings.xpdf_tools_folder = toml.xpdf_tools_folder;
+        settings.poppler_tools_folder = toml.poppler_tools_folder;
+        settings.tesseract_path = toml.tesseract_path;
+        settings.ocr_language = toml.ocr_language;
+        settings.use_text_cache = toml.use_text_cache;
+        settings.max_parallel_extractions = toml.max_parallel_extractions;
+        settings.dehyphenate = toml.dehyphenate;
+        settings.fold_options = FoldOptions {
+            nfkc: toml.unicode_nfkc,
+            ligatures: toml.fold_ligatures,
+            accents: toml.fold_accents,
+            punctuation: toml.fold_punctuation,
+        };
         
         // for color_item in toml.colors {
         //     match color_item.name.as_str() {
@@ -157,6 +517,11 @@ impl Settings {
         stream
     }
     
+    /// Writes the buffers of the searched files to stdout, each in one piece.
+    pub fn create_buffer_writer(&self) -> BufferWriter {
+        BufferWriter::stdout(self.color_choice)
+    }
+
     pub fn create_printer(&self) -> Standard<BufferedStandardStream>{
         // let match_spec_color: UserColorSpec = "match:fg:255,197,12".parse().unwrap();
         // let line_spec_color: UserColorSpec = "line:fg:1,246,238".parse().unwrap();
@@ -165,36 +530,97 @@ impl Settings {
         // let color_specs = ColorSpecs::new(&[match_spec_color, match_spec_underline, line_spec_color, path_spec_color]);
         
         
-        let printer = StandardBuilder::new()
+        self.create_page_printer(BufferedStandardStream::stdout(self.color_choice), 0)
+    }
+
+    /// Printer for one page, the separator between context groups names the page.
+    pub fn create_page_printer<W: WriteColor>(&self, wtr: W, page: usize) -> Standard<W> {
+        StandardBuilder::new()
             .stats(true)
             .heading(true)
             .per_match(true)
             .only_matching(false)
             .per_match_one_line(true)
-            .max_columns(Some(750))
+            .max_columns(Some(MAX_COLUMNS as u64))
             .max_columns_preview(true)
             .color_specs(self.search_color_specs.to_owned())
+            .separator_context(Some(format!("-- Page: {} --", page + 1).into_bytes()))
             //.build(cli::stdout(ColorChoice::Auto));
-            .build(BufferedStandardStream::stdout(ColorChoice::Auto));
-        printer
+            .build(wtr)
     }
 
-    #[allow(dead_code)]
-    pub fn create_summary_printer(&self) -> Summary<BufferedStandardStream> {
+    /// The patterns when each match is labeled with its pattern or its `--fuzzy` distance.
+    pub fn match_labels(&self) -> Option<&PatternSet> {
+        self.patterns.as_ref().filter(|patterns| patterns.has_labels())
+    }
+
+    /// Whether the 0-based `page` is in the `--pages` selection.
+    pub fn page_selected(&self, page: usize) -> bool {
+        self.page_ranges.as_ref().map_or(true, |ranges| ranges.contains(page + 1))
+    }
+
+    pub fn create_matcher(&self, pattern: &str) -> Result<FoldingMatcher, grep::regex::Error> {
+        self.build_matcher(pattern, self.fixed_strings, self.multiline)
+    }
+
+    /// One matcher for all patterns, it matches where any of them matches.
+    pub fn create_matcher_many(&self, patterns: &[String]) -> Result<FoldingMatcher, grep::regex::Error> {
+        self.build_matcher_many(patterns, self.fixed_strings, self.multiline)
+    }
+
+    pub fn build_matcher(&self, pattern: &str, fixed_strings: bool, multiline: bool) -> Result<FoldingMatcher, grep::regex::Error> {
+        self.build_matcher_many(&[pattern], fixed_strings, multiline)
+    }
+
+    pub fn build_matcher_many<P: AsRef<str>>(&self, patterns: &[P], fixed_strings: bool, multiline: bool) -> Result<FoldingMatcher, grep::regex::Error> {
+        let patterns: Vec<String> = patterns.iter().map(|pattern| normalize::fold_text(pattern.as_ref(), &self.fold_options).text).collect();
+        let mut builder = RegexMatcherBuilder::new();
+        builder
+            .case_insensitive(self.case_mode == CaseMode::Insensitive)
+            .case_smart(self.case_mode == CaseMode::Smart)
+            .word(self.word_regexp)
+            .whole_line(self.line_regexp);
+
+        let inner = if multiline {
+            // the phrases are already escaped, so they are never fixed strings
+            let phrases: Vec<String> = patterns.iter().map(|pattern| phrase_pattern(pattern, fixed_strings)).collect();
+            builder.multi_line(true).build_many(&phrases)?
+        } else {
+            builder.fixed_strings(fixed_strings).build_many(&patterns)?
+        };
+        Ok(FoldingMatcher { inner, options: self.fold_options })
+    }
+
+    pub fn create_fuzzy_matcher_many(&self, patterns: &[String], distance: usize) -> Result<FoldingMatcher<FuzzyMatcher>, String> {
+        let patterns: Vec<String> = patterns.iter().map(|pattern| normalize::fold_text(pattern, &self.fold_options).text).collect();
+        let case_insensitive = match self.case_mode {
+            CaseMode::Insensitive => true,
+            CaseMode::Sensitive => false,
+            CaseMode::Smart => !patterns.iter().any(|pattern| pattern.chars().any(char::is_uppercase)),
+        };
+        let inner = FuzzyMatcher::new(&patterns, distance, case_insensitive, self.multiline)?;
+        Ok(FoldingMatcher { inner, options: self.fold_options })
+    }
+
+    pub fn create_summary_printer<W: WriteColor>(&self, kind: SummaryKind, wtr: W) -> Summary<W> {
         let printer = SummaryBuilder::new()
+            .kind(kind)
             .stats(true)
             .path(true)
-            .build(BufferedStandardStream::stdout(ColorChoice::Auto));
+            .color_specs(self.search_color_specs.to_owned())
+            .build(wtr);
 
         printer
     }
 
-    #[allow(dead_code)]
-    pub fn create_searcher() -> Searcher {
+    /// Searcher for a single page, so the context lines stay on that page.
+    pub fn create_searcher(&self) -> Searcher {
         let searcher = SearcherBuilder::new()
             .binary_detection(BinaryDetection::quit(b'\x00'))
-            .multi_line(false)
+            .multi_line(self.multiline)
             .line_number(true)
+            .before_context(self.before_context)
+            .after_context(self.after_context)
             .build();
         
         searcher
@@ -202,7 +628,8 @@ impl Settings {
 }
 impl Default for Settings {
     fn default() -> Self {
-        let color_choice = if std::io::stdin().is_terminal() { ColorChoice::Auto} else { ColorChoice::Never};
+        // termcolor's Auto only looks at TERM, output into a pipe or a file is kept free of escapes here
+        let color_choice = if std::io::stdout().is_terminal() { ColorChoice::Auto} else { ColorChoice::Never};
         // let stream = StandardStream::stdout(color_choice);
 
         Settings {
@@ -213,19 +640,104 @@ impl Default for Settings {
             search_color_specs: ColorSpecs::default_with_color(),
             info_color_spec: ColorSpec::new(),
             use_pdf_tool: AvailablePdfTools::UsePdfDummyTool,
+            pdf_tool_priority: vec![],
             tools: Box::new(PdfDummyTool {}),
             xpdf_tools_folder: None,
+            poppler_tools_folder: None,
+            tesseract_path: None,
+            ocr_language: "eng".into(),
+            use_text_cache: false,
+            max_parallel_extractions: 0,
+            timeout: None,
+            max_filesize: None,
+            max_pages: None,
+            walk: WalkOptions::default(),
+            dehyphenate: true,
+            fold_options: FoldOptions::default(),
             
             cli: None,
             //override from cli(clap) if defined 
             folder_search_mode: FolderSearchMode::ThreadPerFile,
             print_text: true,
             shorten_line_mode: ShortenLineMode::None,
+            output_mode: OutputMode::Standard,
+            case_mode: CaseMode::Insensitive,
+            fixed_strings: false,
+            word_regexp: false,
+            line_regexp: false,
+            multiline: false,
+            page_overlap: 0,
+            page_ranges: None,
+            page_labels: false,
+            query_scope: QueryScope::Page,
+            query: None,
+            patterns: None,
+            tag_patterns: false,
+            fuzzy: None,
+            summary_kind: None,
+            count_pages: false,
+            sort_mode: SortMode::None,
+            verbose: false,
+            before_context: 0,
+            after_context: 0,
             
             color_choice,
         }
     }
 }
+/// Replaces each run of whitespace in the pattern with `\s+`, so a phrase also matches when
+/// the extraction broke it across lines. Whitespace inside a `[...]` class or after `\` is kept.
+pub fn phrase_pattern(pattern: &str, fixed_strings: bool) -> String {
+    if fixed_strings {
+        return pattern.split_whitespace().map(regex::escape).collect::<Vec<_>>().join(r"\s+");
+    }
+
+    let mut result = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    // classes can be nested: [a-z&&[^aeiou]]
+    let mut class_depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                result.push(c);
+                if let Some(next) = chars.next() {
+                    result.push(next);
+                }
+            },
+            // [:alpha:] inside a class
+            '[' if class_depth > 0 && chars.peek() == Some(&':') => {
+                result.push(c);
+                for next in chars.by_ref() {
+                    result.push(next);
+                    if next == ']' {
+                        break;
+                    }
+                }
+            },
+            '[' => {
+                class_depth += 1;
+                result.push(c);
+                if let Some(negate) = chars.next_if_eq(&'^') {
+                    result.push(negate);
+                }
+                // a ']' right at the start is part of the class
+                if let Some(bracket) = chars.next_if_eq(&']') {
+                    result.push(bracket);
+                }
+            },
+            ']' if class_depth > 0 => { class_depth -= 1; result.push(c); },
+            c if c.is_whitespace() && class_depth == 0 => {
+                while chars.peek().is_some_and(|n| n.is_whitespace()) {
+                    chars.next();
+                }
+                result.push_str(r"\s+");
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
+
 #[derive(Debug)]
 pub struct SearchColorSpecs {
     pub match_spec: ColorSpec,
@@ -269,3 +781,46 @@ fn test_merge_settings() {
 }
 
 
+
+#[test]
+fn test_create_matcher() {
+    use grep::matcher::Matcher;
+
+    let mut settings = Settings::default();
+    let is_match = |settings: &Settings, pattern: &str, text: &str| settings.create_matcher(pattern).unwrap().is_match(text.as_bytes()).unwrap();
+
+    assert!(is_match(&settings, "theory", "Field Theory"));
+    settings.case_mode = CaseMode::Sensitive;
+    assert!(!is_match(&settings, "theory", "Field Theory"));
+    settings.case_mode = CaseMode::Smart;
+    assert!(is_match(&settings, "theory", "Field Theory"));
+    assert!(!is_match(&settings, "Theory", "field theory"));
+
+    settings.case_mode = CaseMode::Insensitive;
+    settings.fixed_strings = true;
+    assert!(is_match(&settings, "C++", "written in c++"));
+    assert!(!is_match(&settings, "f(x)", "fx"));
+
+    settings.fixed_strings = false;
+    settings.word_regexp = true;
+    assert!(!is_match(&settings, "theory", "theoryless"));
+    settings.word_regexp = false;
+    settings.line_regexp = true;
+    assert!(!is_match(&settings, "theory", "field theory"));
+    assert!(is_match(&settings, "field theory", "field theory"));
+}
+
+#[test]
+fn test_phrase_pattern() {
+    use grep::matcher::Matcher;
+
+    assert_eq!(phrase_pattern("quantum  field\ttheory", false), r"quantum\s+field\s+theory");
+    assert_eq!(phrase_pattern(r"a\ b[ x]c d", false), r"a\ b[ x]c\s+d");
+    assert_eq!(phrase_pattern("[[:alpha:] ]x y", false), r"[[:alpha:] ]x\s+y");
+    assert_eq!(phrase_pattern("[]a ] b", false), r"[]a ]\s+b");
+    assert_eq!(phrase_pattern("f(x) = y", true), r"f\(x\)\s+=\s+y");
+
+    let settings = Settings { multiline: true, ..Default::default() };
+    let matcher = settings.create_matcher("quantum field theory").unwrap();
+    assert!(matcher.is_match(b"about quantum field\ntheory and").unwrap());
+}
@@ -39,16 +39,67 @@ const CONFIG_FILE_NAME: &str = "config.toml";
 pub struct TomlSettings {
     //colors: ColorsConfig,
     pub use_pdf_tool: AvailablePdfTools,
+    #[serde(default = "default_pdf_tool_priority")]
+    pub pdf_tool_priority: Vec<AvailablePdfTools>,
     pub xpdf_tools_folder: Option<PathBuf>,
+    pub poppler_tools_folder: Option<PathBuf>,
+    pub tesseract_path: Option<PathBuf>,
+    #[serde(default = "default_ocr_language")]
+    pub ocr_language: String,
+    #[serde(default = "default_use_text_cache")]
+    pub use_text_cache: bool,
+    #[serde(default = "default_max_parallel_extractions")]
+    pub max_parallel_extractions: usize,
+    #[serde(default = "default_true")]
+    pub dehyphenate: bool,
+    #[serde(default = "default_true")]
+    pub unicode_nfkc: bool,
+    #[serde(default = "default_true")]
+    pub fold_ligatures: bool,
+    #[serde(default)]
+    pub fold_accents: bool,
+    #[serde(default = "default_true")]
+    pub fold_punctuation: bool,
     pub search_color_specs: Vec<ConfigColorSpec>,
     pub info_color_spec: ConfigColorSpec,
 }
 
+fn default_pdf_tool_priority() -> Vec<AvailablePdfTools> {
+    vec![AvailablePdfTools::UseXpdfTools, AvailablePdfTools::UsePoppler, AvailablePdfTools::UsePdfExtract]
+}
+
+fn default_ocr_language() -> String {
+    "eng".into()
+}
+
+fn default_use_text_cache() -> bool {
+    true
+}
+
+fn default_max_parallel_extractions() -> usize {
+    4
+}
+
+fn default_true() -> bool {
+    true
+}
+
 impl Default for TomlSettings {
     fn default() -> Self {
         TomlSettings {
             xpdf_tools_folder: Some(PathBuf::from("./")),
+            poppler_tools_folder: None,
+            tesseract_path: None,
+            ocr_language: default_ocr_language(),
+            use_text_cache: default_use_text_cache(),
+            max_parallel_extractions: default_max_parallel_extractions(),
+            dehyphenate: true,
+            unicode_nfkc: true,
+            fold_ligatures: true,
+            fold_accents: false,
+            fold_punctuation: true,
             use_pdf_tool: AvailablePdfTools::UseXpdfTools,
+            pdf_tool_priority: default_pdf_tool_priority(),
             search_color_specs: vec![
                 ConfigColorSpec { name: "match".into(), fg: Some((255, 197, 12)), bg: None,
                 styles: vec![
@@ -74,11 +125,13 @@ impl Default for TomlSettings {
     }
 }
 impl TomlSettings {
-   pub fn load() -> Result<TomlSettings, BoxError> {
+   pub fn load(verbose: bool) -> Result<TomlSettings, BoxError> {
         if let Some(proj_dirs) = ProjectDirs::from("", "",  CONFIG_FOLDER_NAME) {
             let mut toml_path = PathBuf::from(proj_dirs.config_dir());
             toml_path.push(CONFIG_FILE_NAME);
-            println!("Load config file from:{:?}", toml_path);
+            if verbose {
+                eprintln!("Load config file from:{:?}", toml_path);
+            }
             let mut read_data = String::new();
             let mut read_file = File::open(&toml_path)?;
             read_file.read_to_string(&mut read_data)?;
@@ -125,7 +178,7 @@ impl TomlSettings {
 
         spec
     }
-    pub fn create_default() -> Result<TomlSettings, BoxError> {
+    pub fn create_default(verbose: bool) -> Result<TomlSettings, BoxError> {
         // let config = TomlSettings {
         //     xpdf_tools_folder: Some(PathBuf::from("./")),
         //     use_pdf_tool: AvailablePdfTools::UseXpdfTools,
@@ -164,7 +217,9 @@ impl TomlSettings {
             toml = format!("{}{}", TomlSettings::toml_help_text(), toml);
             let mut save_file = File::create(&toml_path)?;
             save_file.write_all(toml.as_bytes())?;
-            println!("Config toml created at: {:?}", toml_path);
+            if verbose {
+                eprintln!("Config toml created at: {:?}", toml_path);
+            }
 
         } else {
             return Err("Could not create config file.".into());
@@ -180,8 +235,18 @@ impl TomlSettings {
                       # fg = [R, G, B] # - where R, G, B are the color values as 8-bit integer values [0-255] \n\
                       # bg = [R, G, B] # - delete whole line if no bg color wanted. \n\
                       # styles = [[\"bold\", true], [\"intense\", true], [\"underline\", true]]\n\n\
+                      # use_pdf_tool selects the text backend: \"UseXpdfTools\", \"UsePoppler\" or \"UsePdfExtract\" (built-in, no external binaries)\n\
+                      # pdf_tool_priority lists the backends tried after use_pdf_tool when it is not usable or fails on a file\n\
                       # Use forward slash for path values (windows!)\n\
-                      # xpdf_tools_folder = \"C:/Folder/to/pdfx_tools/binaries/\"\n\n";
+                      # xpdf_tools_folder = \"C:/Folder/to/pdfx_tools/binaries/\"\n\
+                      # poppler_tools_folder = \"C:/Folder/to/poppler/bin/\" # - leave out to use pdftotext/pdfinfo from PATH\n\
+                      # tesseract_path = \"C:/Folder/to/tesseract.exe\" # - OCR for pages without text, leave out to use tesseract from PATH\n\
+                      # ocr_language = \"eng\" # - tesseract language(s), e.g. \"deu+eng\"\n\
+                      # use_text_cache = true # - reuse extracted text of unchanged files, see the 'cache' command\n\
+                      # max_parallel_extractions = 4 # - text extractions (pdftotext, tesseract) running at once, 0 for no limit\n\
+                      # dehyphenate = true # - rejoin words broken with a hyphen at a line end before searching\n\
+                      # unicode_nfkc, fold_ligatures, fold_accents and fold_punctuation fold the text and the pattern before matching:\n\
+                      # NFKC normalization, ligatures like \"fi\" or \"oe\" written out, accents dropped, curly quotes and dashes made ASCII\n\n";
 
         help_text.to_string()
     }
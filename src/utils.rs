@@ -1,42 +1,181 @@
 use std::fmt::Debug;
-use std::{collections::BTreeMap, path::Path};
+use std::io::Read;
+use std::str::FromStr;
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
 
-use walkdir::WalkDir;
-use walkdir::DirEntry;
+use ignore::{overrides::OverrideBuilder, DirEntry, WalkBuilder};
 
-fn is_pdf(entry: &DirEntry) -> bool {
-    entry.file_name()
-         .to_str()
-         .map(|s| s.ends_with(".pdf"))
-         .unwrap_or(false)
+use crate::BoxError;
+
+/// Name of the ignore file of pdf_grep, read like a `.gitignore` in every folder of the walk.
+pub const IGNORE_FILE_NAME: &str = ".pdfgrepignore";
+
+/// Which files of a directory are searched: `--glob`, `--iglob`, `--max-depth`, `--hidden`, `--follow` and `--no-ignore`.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    pub globs: Vec<String>,
+    /// Like `globs`, but matched case insensitively
+    pub iglobs: Vec<String>,
+    pub max_depth: Option<usize>,
+    pub hidden: bool,
+    pub follow: bool,
+    /// Don't leave out the files in `.gitignore`, `.ignore` and the git excludes, `.pdfgrepignore` still counts
+    pub no_ignore: bool,
+    /// More extensions of pdf files besides "pdf", `--type-add`
+    pub extensions: Vec<String>,
+    /// Also take files with another extension or none when they start with a pdf header, `--sniff`
+    pub sniff: bool,
+}
+
+/// Pdf readers accept the `%PDF-` header anywhere in the first 1024 bytes.
+pub fn has_pdf_header(path: &Path) -> bool {
+    let mut head = Vec::with_capacity(1024);
+    std::fs::File::open(path)
+        .and_then(|file| file.take(1024).read_to_end(&mut head))
+        .is_ok_and(|_| head.windows(5).any(|w| w == b"%PDF-"))
+}
+
+fn is_pdf(entry: &DirEntry, walk: &WalkOptions) -> bool {
+    let is_file = entry.file_type().is_some_and(|t| !t.is_dir());
+    let extension = entry.path().extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    is_file && (extension.eq_ignore_ascii_case("pdf")
+        || walk.extensions.iter().any(|ext| extension.eq_ignore_ascii_case(ext))
+        || (walk.sniff && has_pdf_header(entry.path())))
 }
 
-pub fn get_folder_tree(dir_path: &Path) -> BTreeMap<String, Vec<String>> {
+pub fn get_folder_tree(dir_path: &Path, walk: &WalkOptions) -> Result<BTreeMap<String, Vec<String>>, BoxError> {
     let mut pdf_map:BTreeMap<String, Vec<String>> = BTreeMap::new();
     //c:\Data\Library\Books
-    let walk_iter = WalkDir::new(dir_path).into_iter();
-    for entry in walk_iter.map(Result::unwrap).filter(is_pdf ) {
-       
-        pdf_map.entry(entry.path().parent().unwrap().to_str().unwrap().to_string())
-            .and_modify(|v| v.push(entry.path().display().to_string()))
-            .or_insert(vec![entry.path().display().to_string()]);
-        //pdf_files.push(entry.path().display().to_string());
+    for (dir, file) in get_folder_files(dir_path, walk)? {
+        pdf_map.entry(dir).or_default().push(file);
     }
 
-    pdf_map
+    Ok(pdf_map)
 
 }
 
-pub fn get_folder_files(dir_path: &Path) -> Vec<(String, String)> {
+/// The pdf files under `dir_path` as (folder, file). Files matched by a `.gitignore`, `.ignore`, the git
+/// excludes or `.pdfgrepignore` are left out, `--glob` patterns select files and `!pattern` excludes them.
+/// A `--glob` match wins over the ignore files.
+pub fn get_folder_files(dir_path: &Path, walk: &WalkOptions) -> Result<Vec<(String, String)>, BoxError> {
     let mut list:Vec<(String, String)> = vec![];
 
-    let walk_iter = WalkDir::new(dir_path).into_iter();
-    for entry in walk_iter.map(Result::unwrap).filter(is_pdf) {
-       
-       list.push((entry.path().parent().unwrap().to_str().unwrap().to_string(), entry.path().display().to_string()));
+    let mut overrides = OverrideBuilder::new(dir_path);
+    for glob in &walk.globs {
+        overrides.add(glob)?;
     }
+    overrides.case_insensitive(true)?;
+    for glob in &walk.iglobs {
+        overrides.add(glob)?;
+    }
+
+    let walk_iter = WalkBuilder::new(dir_path)
+        .standard_filters(!walk.no_ignore)
+        // the standard filters also cover hidden files and the ignore files of the parent folders
+        .parents(true)
+        .hidden(!walk.hidden)
+        .follow_links(walk.follow)
+        .max_depth(walk.max_depth)
+        // a pdf library is seldom a git repository, its .gitignore still counts
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .overrides(overrides.build()?)
+        .build();
+    for entry in walk_iter {
+        match entry {
+            Ok(entry) if is_pdf(&entry, walk) => {
+                let parent = entry.path().parent().unwrap_or(dir_path);
+                list.push((parent.display().to_string(), entry.path().display().to_string()));
+            },
+            Ok(_) => (),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    Ok(list)
+}
+
+pub const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// Stable 64-bit FNV-1a, for file names and change detection that must survive a rebuild.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3))
+}
 
-    list
+/// Looks for the binary `name` in the given folders, falls back to a `$PATH` lookup.
+pub fn find_tool(folders: &[Option<&Path>], name: &str) -> PathBuf {
+    let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    folders.iter()
+        .flatten()
+        .map(|folder| folder.join(&file_name))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// 1-based page selection like "10-40,55,100-", an open end runs to the last page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRanges(pub Vec<(usize, Option<usize>)>);
+
+impl FromStr for PageRanges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| n.trim().parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(|| format!("invalid page number: '{}'", n.trim()));
+        let mut ranges = vec![];
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let range = match part.split_once('-') {
+                Some((first, "")) => (parse(first)?, None),
+                Some(("", last)) => (1, Some(parse(last)?)),
+                Some((first, last)) => (parse(first)?, Some(parse(last)?)),
+                None => (parse(part)?, Some(parse(part)?)),
+            };
+            if range.1.is_some_and(|last| last < range.0) {
+                return Err(format!("page range runs backwards: '{}'", part));
+            }
+            ranges.push(range);
+        }
+        if ranges.is_empty() {
+            return Err("no pages given".into());
+        }
+        Ok(PageRanges(ranges))
+    }
+}
+
+/// A size in bytes, with an optional K, M or G suffix (1024 based): "500K", "20M".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileSize(pub u64);
+
+impl FromStr for FileSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, factor) = match s.char_indices().last() {
+            Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+            Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+            Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+            _ => (s, 1),
+        };
+        number.trim().parse::<u64>().ok()
+            .and_then(|n| n.checked_mul(factor))
+            .map(FileSize)
+            .ok_or_else(|| format!("invalid size: '{}', e.g. 500K, 20M or 1G", s))
+    }
+}
+
+impl PageRanges {
+    pub fn contains(&self, page: usize) -> bool {
+        self.0.iter().any(|(first, last)| page >= *first && last.map_or(true, |last| page <= last))
+    }
+
+    pub fn first(&self) -> usize {
+        self.0.iter().map(|(first, _)| *first).min().unwrap_or(1)
+    }
+
+    /// Last selected page, `None` when a range is open to the end.
+    pub fn last(&self) -> Option<usize> {
+        self.0.iter().try_fold(0, |max, (_, last)| last.map(|last| max.max(last)))
+    }
 }
 
 #[allow(dead_code)]
@@ -67,4 +206,49 @@ pub fn _dump<I, K, V, A>(iter: I)
             
         }
 
-}
\ No newline at end of file
+}
+#[test]
+fn test_page_ranges() {
+    let ranges: PageRanges = "10-40, 55,100-".parse().unwrap();
+    assert!(ranges.contains(10) && ranges.contains(55) && ranges.contains(500));
+    assert!(!ranges.contains(9) && !ranges.contains(41));
+    assert_eq!((ranges.first(), ranges.last()), (10, None));
+    assert_eq!("-3,7".parse::<PageRanges>().unwrap().last(), Some(7));
+    assert!("5-2".parse::<PageRanges>().is_err());
+    assert!("0".parse::<PageRanges>().is_err());
+}
+
+#[test]
+fn test_file_size() {
+    assert_eq!("512".parse::<FileSize>(), Ok(FileSize(512)));
+    assert_eq!("20M".parse::<FileSize>(), Ok(FileSize(20 * 1024 * 1024)));
+    assert!("M".parse::<FileSize>().is_err() && "1.5G".parse::<FileSize>().is_err());
+}
+
+#[test]
+fn test_folder_files_filters() {
+    let folder = std::env::temp_dir().join(format!("pdf_grep_walk_test_{}", std::process::id()));
+    std::fs::create_dir_all(folder.join("sub")).unwrap();
+    for file in ["a.pdf", "skip.pdf", "notes.txt", "sub/b.pdf", ".hidden.pdf", "REPORT.PDF", "logo.ai", "download"] {
+        std::fs::write(folder.join(file), b"%PDF-1.4").unwrap();
+    }
+    std::fs::write(folder.join("notes.txt"), b"just text").unwrap();
+    std::fs::write(folder.join(IGNORE_FILE_NAME), "skip.pdf\n").unwrap();
+    std::fs::write(folder.join("old.pdf"), b"%PDF-1.4").unwrap();
+    std::fs::write(folder.join(".gitignore"), "old.pdf\n").unwrap();
+
+    let names = |walk: &WalkOptions| {
+        let mut names: Vec<String> = get_folder_files(&folder, walk).unwrap().into_iter()
+            .map(|(_, file)| Path::new(&file).strip_prefix(&folder).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(names(&WalkOptions::default()), ["REPORT.PDF", "a.pdf", "sub/b.pdf"]);
+    assert_eq!(names(&WalkOptions { globs: vec!["!sub/**".into()], hidden: true, ..Default::default() }), [".hidden.pdf", "REPORT.PDF", "a.pdf"]);
+    assert_eq!(names(&WalkOptions { max_depth: Some(1), extensions: vec!["ai".into()], sniff: true, ..Default::default() }), ["REPORT.PDF", "a.pdf", "download", "logo.ai"]);
+    assert_eq!(names(&WalkOptions { iglobs: vec!["B.PDF".into()], ..Default::default() }), ["sub/b.pdf"]);
+    assert_eq!(names(&WalkOptions { max_depth: Some(1), ..Default::default() }), ["REPORT.PDF", "a.pdf"]);
+    assert_eq!(names(&WalkOptions { no_ignore: true, ..Default::default() }), ["REPORT.PDF", "a.pdf", "old.pdf", "sub/b.pdf"]);
+    std::fs::remove_dir_all(&folder).unwrap();
+}